@@ -0,0 +1,437 @@
+//! An `alias` module used to resolve all declared [`KeyAlias`] before the configuration
+//! deserialized into its output type
+//!
+//! The aliases only available while a [`crate::Source`] parsed, the format parsers will use
+//! [`resolve`] (or [`resolve_vars`] for the environment variables) to move each old key
+//! into its new path. Each format resolves its aliases on its own value type, so a YAML tag or
+//! a non-string key will be kept as is
+use std::cell::RefCell;
+
+use rst_common::standard::serde_json::{self, Map};
+
+use crate::types::{ConfigError, Deprecation, KeyAlias};
+
+thread_local! {
+    static CONTEXT: RefCell<Option<Context>> = const { RefCell::new(None) };
+}
+
+struct Context {
+    aliases: Vec<KeyAlias>,
+    deprecated: Vec<KeyAlias>,
+}
+
+/// `scope` used to run given callback with declared aliases, it will return all
+/// deprecated aliases used while the callback running
+pub(crate) fn scope<T>(aliases: Vec<KeyAlias>, cb: impl FnOnce() -> T) -> (T, Vec<KeyAlias>) {
+    let context = Context {
+        aliases,
+        deprecated: Vec::new(),
+    };
+
+    let previous = CONTEXT.with(|ctx| ctx.replace(Some(context)));
+    let out = cb();
+    let context = CONTEXT.with(|ctx| ctx.replace(previous));
+
+    (out, context.map(|ctx| ctx.deprecated).unwrap_or_default())
+}
+
+/// `ignore_deprecation` is a default [`Deprecation`] handler which will do nothing, a library
+/// should not write to the `stderr` by itself, so the caller must supply its own handler
+pub(crate) fn ignore_deprecation(_: &Deprecation) {}
+
+/// `is_enabled` used to check if there are any aliases need to be resolved
+pub(crate) fn is_enabled() -> bool {
+    CONTEXT.with(|ctx| {
+        ctx.borrow()
+            .as_ref()
+            .map(|context| !context.aliases.is_empty())
+            .unwrap_or(false)
+    })
+}
+
+/// `Tree` is a configuration tree of each format, which is a table of string keys
+pub(crate) trait Tree: Sized + PartialEq {
+    fn child(&self, key: &str) -> Option<&Self>;
+    fn child_mut(&mut self, key: &str) -> Option<&mut Self>;
+    fn remove_child(&mut self, key: &str) -> Option<Self>;
+    fn is_empty_table(&self) -> bool;
+
+    /// `child_table` used to get a child, or insert an empty table when it's missing, it will
+    /// return `None` when this tree is not a table
+    fn child_table(&mut self, key: &str) -> Option<&mut Self>;
+
+    /// `set_child` used to insert a child, it will return `false` when this tree is not a table
+    fn set_child(&mut self, key: &str, value: Self) -> bool;
+}
+
+impl Tree for serde_json::Value {
+    fn child(&self, key: &str) -> Option<&Self> {
+        self.as_object()?.get(key)
+    }
+
+    fn child_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_object_mut()?.get_mut(key)
+    }
+
+    fn remove_child(&mut self, key: &str) -> Option<Self> {
+        self.as_object_mut()?.remove(key)
+    }
+
+    fn is_empty_table(&self) -> bool {
+        self.as_object().is_some_and(Map::is_empty)
+    }
+
+    fn child_table(&mut self, key: &str) -> Option<&mut Self> {
+        let child = self
+            .as_object_mut()?
+            .entry(key)
+            .or_insert_with(|| Self::Object(Map::new()));
+
+        Some(child)
+    }
+
+    fn set_child(&mut self, key: &str, value: Self) -> bool {
+        self.as_object_mut()
+            .map(|object| object.insert(key.to_string(), value))
+            .is_some()
+    }
+}
+
+impl Tree for toml::Value {
+    fn child(&self, key: &str) -> Option<&Self> {
+        self.as_table()?.get(key)
+    }
+
+    fn child_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_table_mut()?.get_mut(key)
+    }
+
+    fn remove_child(&mut self, key: &str) -> Option<Self> {
+        self.as_table_mut()?.remove(key)
+    }
+
+    fn is_empty_table(&self) -> bool {
+        self.as_table().is_some_and(toml::Table::is_empty)
+    }
+
+    fn child_table(&mut self, key: &str) -> Option<&mut Self> {
+        let child = self
+            .as_table_mut()?
+            .entry(key)
+            .or_insert_with(|| Self::Table(toml::Table::new()));
+
+        Some(child)
+    }
+
+    fn set_child(&mut self, key: &str, value: Self) -> bool {
+        self.as_table_mut()
+            .map(|table| table.insert(key.to_string(), value))
+            .is_some()
+    }
+}
+
+impl Tree for serde_yaml::Value {
+    fn child(&self, key: &str) -> Option<&Self> {
+        self.as_mapping()?.get(key)
+    }
+
+    fn child_mut(&mut self, key: &str) -> Option<&mut Self> {
+        self.as_mapping_mut()?.get_mut(key)
+    }
+
+    fn remove_child(&mut self, key: &str) -> Option<Self> {
+        self.as_mapping_mut()?.remove(key)
+    }
+
+    fn is_empty_table(&self) -> bool {
+        self.as_mapping().is_some_and(serde_yaml::Mapping::is_empty)
+    }
+
+    fn child_table(&mut self, key: &str) -> Option<&mut Self> {
+        let child = self
+            .as_mapping_mut()?
+            .entry(Self::String(key.to_string()))
+            .or_insert_with(|| Self::Mapping(serde_yaml::Mapping::new()));
+
+        Some(child)
+    }
+
+    fn set_child(&mut self, key: &str, value: Self) -> bool {
+        self.as_mapping_mut()
+            .map(|mapping| mapping.insert(Self::String(key.to_string()), value))
+            .is_some()
+    }
+}
+
+/// `resolve` used to move all declared alias paths to their new paths from given configuration tree
+pub(crate) fn resolve<T: Tree>(mut tree: T) -> Result<T, ConfigError> {
+    CONTEXT.with(|ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let context = match ctx.as_mut() {
+            Some(context) => context,
+            None => return Ok(tree),
+        };
+
+        for alias in context.aliases.iter() {
+            let from: Vec<&str> = alias.from.split('.').collect();
+            let to: Vec<&str> = alias.to.split('.').collect();
+
+            let old = match take(&mut tree, &from) {
+                Some(old) => old,
+                None => continue,
+            };
+
+            match get(&tree, &to) {
+                Some(current) if current != &old => {
+                    return Err(ConfigError::AliasError(format!(
+                        "`{}` conflicts with `{}`",
+                        alias.from, alias.to
+                    )))
+                }
+                Some(_) => {}
+                None => insert(&mut tree, &to, old)?,
+            }
+
+            if alias.deprecated {
+                context.deprecated.push(alias.clone());
+            }
+        }
+
+        Ok(tree)
+    })
+}
+
+/// `resolve_vars` used to rename all declared alias keys from given environment variables
+///
+/// Since environment variables are a flat key and value, each alias path will be converted into
+/// its variable key form before compared, example: `db.max_conns` will match `DB_MAX_CONNS`.
+/// The variable keys given here already stripped from their prefix by the `Env`
+/// parser, so the alias paths must not include the prefix
+pub(crate) fn resolve_vars(
+    mut vars: Vec<(String, String)>,
+) -> Result<Vec<(String, String)>, ConfigError> {
+    CONTEXT.with(|ctx| {
+        let mut ctx = ctx.borrow_mut();
+        let context = match ctx.as_mut() {
+            Some(context) => context,
+            None => return Ok(vars),
+        };
+
+        for alias in context.aliases.iter() {
+            let from = env_key(&alias.from);
+            let to = env_key(&alias.to);
+
+            let position = match vars
+                .iter()
+                .position(|(key, _)| key.eq_ignore_ascii_case(&from))
+            {
+                Some(position) => position,
+                None => continue,
+            };
+
+            let (_, old) = vars.remove(position);
+            let current = vars.iter().find(|(key, _)| key.eq_ignore_ascii_case(&to));
+
+            match current {
+                Some((_, value)) if value != &old => {
+                    return Err(ConfigError::AliasError(format!(
+                        "`{}` conflicts with `{}`",
+                        alias.from, alias.to
+                    )))
+                }
+                Some(_) => {}
+                None => vars.push((to, old)),
+            }
+
+            if alias.deprecated {
+                context.deprecated.push(alias.clone());
+            }
+        }
+
+        Ok(vars)
+    })
+}
+
+/// `env_key` used to convert an alias path into its environment variable key
+fn env_key(path: &str) -> String {
+    path.replace('.', "_").to_uppercase()
+}
+
+fn get<'a, T: Tree>(tree: &'a T, path: &[&str]) -> Option<&'a T> {
+    path.iter()
+        .try_fold(tree, |current, key| current.child(key))
+}
+
+/// `take` will remove a value from given path, including for all of its empty parents
+fn take<T: Tree>(tree: &mut T, path: &[&str]) -> Option<T> {
+    match path {
+        [] => None,
+        [key] => tree.remove_child(key),
+        [key, rest @ ..] => {
+            let child = tree.child_mut(key)?;
+            let value = take(child, rest)?;
+
+            if child.is_empty_table() {
+                tree.remove_child(key);
+            }
+
+            Some(value)
+        }
+    }
+}
+
+fn insert<T: Tree>(tree: &mut T, path: &[&str], value: T) -> Result<(), ConfigError> {
+    let not_table = || {
+        ConfigError::AliasError(format!(
+            "unable to set `{}`, its parent is not a table",
+            path.join(".")
+        ))
+    };
+
+    match path {
+        [] => Ok(()),
+        [key] => match tree.set_child(key, value) {
+            true => Ok(()),
+            false => Err(not_table()),
+        },
+        [key, rest @ ..] => {
+            let child = tree.child_table(key).ok_or_else(not_table)?;
+            insert(child, rest, value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rst_common::standard::serde_json::json;
+
+    fn aliases() -> Vec<KeyAlias> {
+        vec![
+            KeyAlias::new(
+                "db.max_conns".to_string(),
+                "db.pool.max_connections".to_string(),
+            ),
+            KeyAlias::deprecated("timeout".to_string(), "db.timeout".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_resolve_moved_keys() {
+        let tree = json!({"db": {"max_conns": 10, "host": "localhost"}, "timeout": 5});
+        let (result, deprecated) = scope(aliases(), || resolve(tree));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            json!({"db": {"host": "localhost", "pool": {"max_connections": 10}, "timeout": 5}})
+        );
+        assert_eq!(
+            deprecated,
+            vec![KeyAlias::deprecated(
+                "timeout".to_string(),
+                "db.timeout".to_string()
+            )]
+        )
+    }
+
+    #[test]
+    fn test_resolve_remove_empty_parents() {
+        let tree = json!({"db": {"max_conns": 10}});
+        let (result, deprecated) = scope(aliases(), || resolve(tree));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            json!({"db": {"pool": {"max_connections": 10}}})
+        );
+        assert!(deprecated.is_empty())
+    }
+
+    #[test]
+    fn test_resolve_same_values() {
+        let tree = json!({"db": {"max_conns": 10, "pool": {"max_connections": 10}}});
+        let (result, _) = scope(aliases(), || resolve(tree));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            json!({"db": {"pool": {"max_connections": 10}}})
+        )
+    }
+
+    #[test]
+    fn test_resolve_conflicted_values() {
+        let tree = json!({"db": {"max_conns": 10, "pool": {"max_connections": 20}}});
+        let (result, _) = scope(aliases(), || resolve(tree));
+
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            ConfigError::AliasError(
+                "`db.max_conns` conflicts with `db.pool.max_connections`".to_string()
+            )
+        )
+    }
+
+    #[test]
+    fn test_resolve_vars() {
+        let aliases = vec![KeyAlias::deprecated(
+            "max_conns".to_string(),
+            "pool_max".to_string(),
+        )];
+        let vars = vec![("MAX_CONNS".to_string(), "10".to_string())];
+        let (result, deprecated) = scope(aliases, || resolve_vars(vars));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            vec![("POOL_MAX".to_string(), "10".to_string())]
+        );
+        assert_eq!(deprecated.len(), 1)
+    }
+
+    #[test]
+    fn test_resolve_vars_nested_paths() {
+        let vars = vec![
+            ("DB_MAX_CONNS".to_string(), "10".to_string()),
+            ("DB_HOST".to_string(), "localhost".to_string()),
+        ];
+        let (result, _) = scope(aliases(), || resolve_vars(vars));
+
+        assert!(result.is_ok());
+        assert_eq!(
+            result.unwrap(),
+            vec![
+                ("DB_HOST".to_string(), "localhost".to_string()),
+                ("DB_POOL_MAX_CONNECTIONS".to_string(), "10".to_string())
+            ]
+        )
+    }
+
+    #[test]
+    fn test_resolve_vars_conflicted_values() {
+        let aliases = vec![KeyAlias::new(
+            "max_conns".to_string(),
+            "pool_max".to_string(),
+        )];
+        let vars = vec![
+            ("MAX_CONNS".to_string(), "10".to_string()),
+            ("POOL_MAX".to_string(), "20".to_string()),
+        ];
+        let (result, _) = scope(aliases, || resolve_vars(vars));
+
+        assert!(result.is_err())
+    }
+
+    #[test]
+    fn test_is_enabled() {
+        assert!(!is_enabled());
+
+        let (enabled, _) = scope(aliases(), is_enabled);
+        assert!(enabled);
+
+        let (enabled, _) = scope(Vec::new(), is_enabled);
+        assert!(!enabled)
+    }
+}
@@ -1,6 +1,8 @@
 use std::marker::PhantomData;
 
-use crate::types::{ConfigError, SourceFormatter, SourceParser};
+use crate::types::{
+    ConfigError, Deprecation, KeyAlias, SourceFormatter, SourceParser, StrictMode, UnusedKey,
+};
 use crate::Source;
 
 /// Builder is a main object used to manage multiple source parser
@@ -17,6 +19,26 @@ use crate::Source;
 ///  .fetch()?
 ///  .as_yaml()?;
 /// ```
+///
+/// Use [`Builder::strict`] to report all configuration keys that not consumed
+/// by any fields:
+///
+/// ```rust
+/// let cfg: Message = Builder::new(from_file(toml_file))
+///  .strict(StrictMode::Deny)
+///  .fetch()?
+///  .parse(use_toml)?;
+/// ```
+///
+/// Use [`Builder::alias`] or [`Builder::deprecate`] to keep accepting an old key
+/// after it renamed:
+///
+/// ```rust
+/// let cfg: Config = Builder::new(from_file(toml_file))
+///  .deprecate("db.max_conns".to_string(), "db.pool.max_connections".to_string())
+///  .fetch()?
+///  .parse(use_toml)?;
+/// ```
 pub struct Builder<TParser, TFormatter, TValue>
 where
     TValue: Clone,
@@ -24,6 +46,10 @@ where
     TParser: for<'a> SourceParser<TFormatter, TValue>,
 {
     adapter: TParser,
+    strict: Option<StrictMode>,
+    aliases: Vec<KeyAlias>,
+    on_deprecation: Option<fn(&Deprecation)>,
+    on_unused_key: Option<fn(&UnusedKey)>,
     _phantomf: Option<PhantomData<TFormatter>>,
     _phantomf2: Option<PhantomData<TValue>>,
 }
@@ -37,13 +63,64 @@ where
     pub fn new(adapter: T) -> Self {
         Self {
             adapter,
+            strict: None,
+            aliases: Vec::new(),
+            on_deprecation: None,
+            on_unused_key: None,
             _phantomf: None,
             _phantomf2: None,
         }
     }
 
+    pub fn strict(mut self, mode: StrictMode) -> Self {
+        self.strict = Some(mode);
+        self
+    }
+
+    /// `alias` used to accept the `from` key path as the `to` key path
+    pub fn alias(mut self, from: String, to: String) -> Self {
+        self.aliases.push(KeyAlias::new(from, to));
+        self
+    }
+
+    /// `deprecate` used to accept the `from` key path as the `to` key path, and
+    /// emit a [`Deprecation`] warning each time the `from` key path used
+    pub fn deprecate(mut self, from: String, to: String) -> Self {
+        self.aliases.push(KeyAlias::deprecated(from, to));
+        self
+    }
+
+    pub fn on_deprecation(mut self, handler: fn(&Deprecation)) -> Self {
+        self.on_deprecation = Some(handler);
+        self
+    }
+
+    /// `on_unused_key` used to handle each [`UnusedKey`] warning emitted by [`StrictMode::Warn`]
+    pub fn on_unused_key(mut self, handler: fn(&UnusedKey)) -> Self {
+        self.on_unused_key = Some(handler);
+        self
+    }
+
     pub fn fetch(&self) -> Result<Source<F, St>, ConfigError> {
-        self.adapter.fetch()
+        let mut source = self
+            .adapter
+            .fetch()?
+            .with_name(self.adapter.name())
+            .with_aliases(self.aliases.clone());
+
+        if let Some(mode) = self.strict {
+            source = source.with_strict(mode);
+        }
+
+        if let Some(handler) = self.on_deprecation {
+            source = source.on_deprecation(handler);
+        }
+
+        if let Some(handler) = self.on_unused_key {
+            source = source.on_unused_key(handler);
+        }
+
+        Ok(source)
     }
 }
 
@@ -52,11 +129,13 @@ mod tests {
     use super::*;
     use std::env;
     use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     use rst_common::standard::serde::{self, Deserialize, Serialize};
 
     use crate::format::{use_env, use_json, use_toml, use_yaml};
     use crate::parser::{from_env, from_file};
+    use crate::testing::MemoryEnv;
 
     #[derive(Serialize, Deserialize, Debug, Clone)]
     #[serde(crate = "self::serde")]
@@ -71,6 +150,20 @@ mod tests {
         keys: MessageGroupKeys,
     }
 
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[serde(crate = "self::serde")]
+    struct Greeting {
+        greeting: String,
+        keys: MessageGroupKeys,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, Clone)]
+    #[serde(crate = "self::serde")]
+    struct PoolConfig {
+        db_host: String,
+        db_pool_max_connections: u32,
+    }
+
     #[derive(Serialize, Deserialize, Debug, Clone)]
     #[serde(crate = "self::serde")]
     struct MessageGroupKeys {
@@ -124,14 +217,91 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parser_file_strict_deny() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("fixtures");
+
+        let toml_file = format!("{}/test.toml", path.display());
+        let cfg: Result<Message, ConfigError> = Builder::new(from_file(toml_file.clone()))
+            .strict(StrictMode::Deny)
+            .fetch()
+            .and_then(|source| source.parse(use_toml));
+
+        assert!(cfg.is_err());
+        assert_eq!(
+            cfg.unwrap_err(),
+            ConfigError::StrictError(format!("`keys` (source: {})", toml_file))
+        )
+    }
+
+    #[test]
+    fn test_parser_file_strict_consumed() -> Result<(), ConfigError> {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("fixtures");
+
+        let json_file = format!("{}/test.json", path.display());
+        let cfg: MessageGroup = Builder::new(from_file(json_file))
+            .strict(StrictMode::Deny)
+            .fetch()?
+            .parse(use_json)?;
+
+        assert_eq!(cfg.clone().message, "hello world");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parser_file_deprecated_key() -> Result<(), ConfigError> {
+        static CALLED: AtomicUsize = AtomicUsize::new(0);
+
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("fixtures");
+
+        let json_file = format!("{}/test.json", path.display());
+        let cfg: Greeting = Builder::new(from_file(json_file))
+            .strict(StrictMode::Deny)
+            .deprecate("message".to_string(), "greeting".to_string())
+            .on_deprecation(|deprecation| {
+                assert_eq!(deprecation.key, "message".to_string());
+                assert_eq!(deprecation.replacement, "greeting".to_string());
+                CALLED.fetch_add(1, Ordering::SeqCst);
+            })
+            .fetch()?
+            .parse(use_json)?;
+
+        assert_eq!(cfg.clone().greeting, "hello world");
+        assert_eq!(cfg.clone().keys.key1, "value1");
+        assert_eq!(CALLED.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
     #[test]
     fn test_parser_env_vars() -> Result<(), ConfigError> {
-        env::set_var("TEST_MESSAGE", "hello world");
-        let cfg: Message = Builder::new(from_env("TEST_".to_string()))
+        let provider = MemoryEnv::new().with_var("TEST_MESSAGE", "hello world");
+        let cfg: Message = Builder::new(from_env("TEST_".to_string()).with_provider(provider))
             .fetch()?
             .parse(use_env)?;
 
         assert_eq!(cfg.clone().message, "hello world");
         Ok(())
     }
+
+    #[test]
+    fn test_parser_env_nested_alias() -> Result<(), ConfigError> {
+        let provider = MemoryEnv::new()
+            .with_var("APP_DB_HOST", "localhost")
+            .with_var("APP_DB_MAX_CONNS", "10");
+        let cfg: PoolConfig = Builder::new(from_env("APP_".to_string()).with_provider(provider))
+            .strict(StrictMode::Deny)
+            .alias(
+                "db.max_conns".to_string(),
+                "db.pool.max_connections".to_string(),
+            )
+            .fetch()?
+            .parse(use_env)?;
+
+        assert_eq!(cfg.clone().db_host, "localhost");
+        assert_eq!(cfg.clone().db_pool_max_connections, 10);
+        Ok(())
+    }
 }
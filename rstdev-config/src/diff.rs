@@ -0,0 +1,171 @@
+//! A `diff` module used to compare two resolved configuration trees
+//!
+//! The configuration tree is a `serde_json::Value`, each nested table will be compared
+//! recursively and all other values (including for the arrays) will be compared as a single value.
+//! Each [`Change`] will have a dotted key path, example: `log.level`
+use rst_common::standard::serde::Serialize;
+use rst_common::standard::serde_json::{self, Map, Value};
+
+use crate::types::ConfigError;
+
+/// `Change` is a single changed key path between two configuration trees
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added {
+        path: String,
+        value: Value,
+    },
+    Removed {
+        path: String,
+        value: Value,
+    },
+    Changed {
+        path: String,
+        old: Value,
+        new: Value,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. } => path,
+            Change::Removed { path, .. } => path,
+            Change::Changed { path, .. } => path,
+        }
+    }
+
+    /// `is_under` used to check if this change belongs to given key prefix, example:
+    /// `log.level` belongs to `log` and `log.level` but not to `log.lev`
+    pub fn is_under(&self, prefix: &str) -> bool {
+        let path = self.path();
+        prefix.is_empty()
+            || path == prefix
+            || (path.starts_with(prefix) && path[prefix.len()..].starts_with('.'))
+    }
+}
+
+/// `diff` used to compare two configuration trees, all changes will be sorted by their paths
+pub fn diff(old: &Value, new: &Value) -> Vec<Change> {
+    let mut changes = Vec::new();
+    compare(String::new(), old, new, &mut changes);
+
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+/// `diff_values` used to compare two typed configuration values, both of them will be
+/// serialized as configuration trees before compared
+pub fn diff_values<T>(old: &T, new: &T) -> Result<Vec<Change>, ConfigError>
+where
+    T: Serialize,
+{
+    let old = serde_json::to_value(old).map_err(|err| ConfigError::FormatError(err.to_string()))?;
+    let new = serde_json::to_value(new).map_err(|err| ConfigError::FormatError(err.to_string()))?;
+
+    Ok(diff(&old, &new))
+}
+
+fn compare(path: String, old: &Value, new: &Value, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => compare_tables(path, old, new, changes),
+        _ if old != new => changes.push(Change::Changed {
+            path,
+            old: old.clone(),
+            new: new.clone(),
+        }),
+        _ => {}
+    }
+}
+
+fn compare_tables(
+    path: String,
+    old: &Map<String, Value>,
+    new: &Map<String, Value>,
+    changes: &mut Vec<Change>,
+) {
+    for (key, old_value) in old.iter() {
+        let key_path = join(&path, key);
+        match new.get(key) {
+            Some(new_value) => compare(key_path, old_value, new_value, changes),
+            None => changes.push(Change::Removed {
+                path: key_path,
+                value: old_value.clone(),
+            }),
+        }
+    }
+
+    for (key, new_value) in new.iter() {
+        if !old.contains_key(key) {
+            changes.push(Change::Added {
+                path: join(&path, key),
+                value: new_value.clone(),
+            });
+        }
+    }
+}
+
+fn join(path: &str, key: &str) -> String {
+    if path.is_empty() {
+        return key.to_string();
+    }
+
+    format!("{}.{}", path, key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rst_common::standard::serde_json::json;
+
+    #[test]
+    fn test_diff() {
+        let old = json!({"log": {"level": "info", "format": "json"}, "db": {"hosts": ["a"]}});
+        let new = json!({"log": {"level": "debug"}, "db": {"hosts": ["a", "b"]}, "port": 80});
+
+        let changes = diff(&old, &new);
+        assert_eq!(
+            changes,
+            vec![
+                Change::Changed {
+                    path: "db.hosts".to_string(),
+                    old: json!(["a"]),
+                    new: json!(["a", "b"])
+                },
+                Change::Removed {
+                    path: "log.format".to_string(),
+                    value: json!("json")
+                },
+                Change::Changed {
+                    path: "log.level".to_string(),
+                    old: json!("info"),
+                    new: json!("debug")
+                },
+                Change::Added {
+                    path: "port".to_string(),
+                    value: json!(80)
+                },
+            ]
+        )
+    }
+
+    #[test]
+    fn test_diff_same_trees() {
+        let tree = json!({"log": {"level": "info"}});
+        assert!(diff(&tree, &tree).is_empty())
+    }
+
+    #[test]
+    fn test_change_is_under() {
+        let change = Change::Added {
+            path: "log.level".to_string(),
+            value: json!("info"),
+        };
+
+        assert!(change.is_under(""));
+        assert!(change.is_under("log"));
+        assert!(change.is_under("log.level"));
+        assert!(!change.is_under("log.lev"));
+        assert!(!change.is_under("db"))
+    }
+}
@@ -0,0 +1,292 @@
+//! A `flags` module provides a feature flag evaluator on top of the configuration sources
+//!
+//! Flag definitions are a common configuration value, so they can be loaded through any available
+//! sources and formats. Example using `TOML`:
+//!
+//! ```toml
+//! [flags.new_checkout]
+//! enabled = true
+//! rollout = 25.0
+//! allow = ["tenant-1"]
+//! deny = ["tenant-9"]
+//! ```
+//!
+//! The [`Flags`] evaluator will always read the flag definitions from a [`Handle`], which means
+//! all flags will be re-evaluated based on the latest definitions after the configuration reloaded
+use std::collections::HashMap;
+
+use rst_common::standard::serde::{self, de, Deserialize, Deserializer, Serialize};
+
+use crate::Handle;
+
+/// `FlagSet` is a collection of [`Flag`] definitions indexed by their names
+pub type FlagSet = HashMap<String, Flag>;
+
+/// `Flag` is a single feature flag definition
+///
+/// - `enabled` is a main switch, a disabled flag will always be evaluated as `false`
+/// - `rollout` is an optional percentage (`0.0` - `100.0`) of keys that will get the flag enabled,
+///   any other values (including `NaN`) will be rejected while loading the flags
+/// - `allow` is a list of keys that will always get the flag enabled
+/// - `deny` is a list of keys that will never get the flag enabled, it has higher priority than `allow`
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(crate = "self::serde")]
+pub struct Flag {
+    #[serde(default)]
+    pub enabled: bool,
+
+    #[serde(default, deserialize_with = "deserialize_rollout")]
+    pub rollout: Option<f64>,
+
+    #[serde(default)]
+    pub allow: Vec<String>,
+
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl Flag {
+    /// `is_enabled` used to evaluate this flag without any keys
+    ///
+    /// A flag with a percentage rollout can only be enabled for a key, so it will always
+    /// be evaluated as `false` unless the rollout is `100.0`
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.evaluate(name, None)
+    }
+
+    /// `is_enabled_for` used to evaluate this flag for given key, like a user or tenant id
+    ///
+    /// The percentage rollout used a stable hashing of the flag name and the key, so the
+    /// same key will always get the same result as long as the rollout is not changed
+    pub fn is_enabled_for(&self, name: &str, key: &str) -> bool {
+        self.evaluate(name, Some(key))
+    }
+
+    fn evaluate(&self, name: &str, key: Option<&str>) -> bool {
+        if !self.enabled {
+            return false;
+        }
+
+        if let Some(key) = key {
+            if self.deny.iter().any(|denied| denied == key) {
+                return false;
+            }
+
+            if self.allow.iter().any(|allowed| allowed == key) {
+                return true;
+            }
+        }
+
+        match self.rollout {
+            None => true,
+            Some(rollout) if rollout >= 100.0 => true,
+            Some(rollout) => key
+                .map(|key| (bucket(name, key) as f64) < rollout * 100.0)
+                .unwrap_or(false),
+        }
+    }
+}
+
+fn deserialize_rollout<'de, D>(deserializer: D) -> Result<Option<f64>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    match Option::<f64>::deserialize(deserializer)? {
+        Some(rollout) if !(0.0..=100.0).contains(&rollout) => Err(de::Error::custom(format!(
+            "invalid rollout {}, expected a percentage between 0.0 and 100.0",
+            rollout
+        ))),
+        rollout => Ok(rollout),
+    }
+}
+
+/// `Flags` is a feature flag evaluator that read its flag definitions from a [`Handle`]
+///
+/// The `select` function used to get the [`FlagSet`] from the configuration value, so the flag
+/// definitions can be a part of a bigger configuration object.
+///
+/// Example:
+///
+/// ```rust
+/// let handle = Handle::new(cfg);
+/// let flags = Flags::new(handle.clone(), |cfg: &Config| &cfg.flags);
+///
+/// if flags.is_enabled_for("new_checkout", "tenant-1") {
+///     // ...
+/// }
+/// ```
+pub struct Flags<T> {
+    handle: Handle<T>,
+    select: fn(&T) -> &FlagSet,
+}
+
+impl<T> Clone for Flags<T> {
+    fn clone(&self) -> Self {
+        Self {
+            handle: self.handle.clone(),
+            select: self.select,
+        }
+    }
+}
+
+impl<T> Flags<T> {
+    pub fn new(handle: Handle<T>, select: fn(&T) -> &FlagSet) -> Self {
+        Self { handle, select }
+    }
+
+    /// `is_enabled` used to evaluate a flag without any keys, an unknown flag
+    /// will always be evaluated as `false`
+    pub fn is_enabled(&self, name: &str) -> bool {
+        let snapshot = self.handle.snapshot();
+        (self.select)(&snapshot)
+            .get(name)
+            .map(|flag| flag.is_enabled(name))
+            .unwrap_or(false)
+    }
+
+    /// `is_enabled_for` used to evaluate a flag for given key, an unknown flag
+    /// will always be evaluated as `false`
+    pub fn is_enabled_for(&self, name: &str, key: &str) -> bool {
+        let snapshot = self.handle.snapshot();
+        (self.select)(&snapshot)
+            .get(name)
+            .map(|flag| flag.is_enabled_for(name, key))
+            .unwrap_or(false)
+    }
+}
+
+/// `bucket` used to map the flag name and the key into a stable bucket between `0` and `9999`
+///
+/// It's using `FNV-1a` hashing algorithm, rather than the std `DefaultHasher` which its
+/// algorithm is not guaranteed to be stable between Rust releases
+fn bucket(name: &str, key: &str) -> u64 {
+    const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+    const FNV_PRIME: u64 = 0x100000001b3;
+
+    let hash = name
+        .bytes()
+        .chain(":".bytes())
+        .chain(key.bytes())
+        .fold(FNV_OFFSET, |hash, byte| {
+            (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+        });
+
+    hash % 10000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::format::use_toml;
+    use crate::types::ConfigError;
+    use crate::values::StringValue;
+    use crate::Source;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(crate = "self::serde")]
+    struct Config {
+        flags: FlagSet,
+    }
+
+    fn load(input: &str) -> Config {
+        Source::new(StringValue::new(input.to_string()))
+            .parse(use_toml)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_boolean_flags() {
+        let cfg = load(
+            r#"
+            [flags.enabled]
+            enabled = true
+
+            [flags.disabled]
+            enabled = false
+            allow = ["tenant-1"]
+            "#,
+        );
+
+        let flags = Flags::new(Handle::new(cfg), |cfg: &Config| &cfg.flags);
+        assert!(flags.is_enabled("enabled"));
+        assert!(flags.is_enabled_for("enabled", "tenant-1"));
+        assert!(!flags.is_enabled("disabled"));
+        assert!(!flags.is_enabled_for("disabled", "tenant-1"));
+        assert!(!flags.is_enabled("unknown"))
+    }
+
+    #[test]
+    fn test_allow_and_deny_lists() {
+        let flag = Flag {
+            enabled: true,
+            rollout: Some(0.0),
+            allow: vec!["tenant-1".to_string(), "tenant-2".to_string()],
+            deny: vec!["tenant-2".to_string()],
+        };
+
+        assert!(flag.is_enabled_for("checkout", "tenant-1"));
+        assert!(!flag.is_enabled_for("checkout", "tenant-2"));
+        assert!(!flag.is_enabled_for("checkout", "tenant-3"));
+        assert!(!flag.is_enabled("checkout"))
+    }
+
+    #[test]
+    fn test_percentage_rollout() {
+        let flag = Flag {
+            enabled: true,
+            rollout: Some(25.0),
+            ..Flag::default()
+        };
+
+        let enabled = (0..10000)
+            .filter(|id| flag.is_enabled_for("checkout", &format!("user-{}", id)))
+            .count();
+
+        assert!(enabled > 2300 && enabled < 2700);
+        assert_eq!(
+            flag.is_enabled_for("checkout", "user-1"),
+            flag.is_enabled_for("checkout", "user-1")
+        )
+    }
+
+    #[test]
+    fn test_invalid_rollout() {
+        for rollout in ["-1.0", "100.5", "nan"] {
+            let result: Result<Config, ConfigError> = Source::new(StringValue::new(format!(
+                "[flags.checkout]\nenabled = true\nrollout = {}",
+                rollout
+            )))
+            .parse(use_toml);
+            assert!(
+                matches!(result, Err(ConfigError::FormatError(msg)) if msg.contains("invalid rollout"))
+            );
+        }
+
+        let cfg = load("[flags.checkout]\nenabled = true\nrollout = 100.0");
+        assert_eq!(cfg.flags["checkout"].rollout, Some(100.0))
+    }
+
+    #[test]
+    fn test_stable_bucket() {
+        assert_eq!(bucket("checkout", "user-1"), 3872);
+        assert_eq!(bucket("checkout", "user-1"), bucket("checkout", "user-1"))
+    }
+
+    #[test]
+    fn test_reevaluate_after_reload() {
+        let handle = Handle::new(load("[flags.checkout]\nenabled = false"));
+        let flags = Flags::new(handle.clone(), |cfg: &Config| &cfg.flags);
+        assert!(!flags.is_enabled("checkout"));
+
+        let result = handle.reload(|| {
+            Source::new(StringValue::new(
+                "[flags.checkout]\nenabled = true".to_string(),
+            ))
+            .parse(use_toml)
+        });
+
+        assert!(result.is_ok());
+        assert!(flags.is_enabled("checkout"))
+    }
+}
@@ -0,0 +1,433 @@
+//! `env` provides a `serde` deserializer for a collection of environment variables
+//!
+//! Each variable key will be lowercased before matched with the field names, and each value
+//! will be parsed based on its requested field type. Beside of primitive values, it also support:
+//!
+//! - List values using configurable delimiter, example: `APP_HOSTS=a,b,c`
+//! - Map values using `KEY=VALUE` syntax, example: `APP_LABELS=team=core,tier=web`
+//! - Inline JSON values for complex fields, example: `APP_LIMITS={"a":1}` or `APP_MATRIX=[[1,2],[3]]`
+//!
+//! Only a list, map or struct field will be parsed as JSON, a string field will always keep its raw value
+use std::str::FromStr;
+
+use rst_common::standard::serde::de::value::{
+    Error, MapDeserializer, SeqDeserializer, StringDeserializer,
+};
+use rst_common::standard::serde::de::{self, Deserializer, IntoDeserializer, Visitor};
+use rst_common::standard::serde::forward_to_deserialize_any;
+use rst_common::standard::serde_json;
+
+/// `EnvOptions` used to configure how environment variable values will be parsed
+///
+/// - `list_delimiter` used to split list values and also map entries, default to `,`
+/// - `kv_delimiter` used to split a key and its value for each map entry, default to `=`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct EnvOptions {
+    pub list_delimiter: char,
+    pub kv_delimiter: char,
+}
+
+impl Default for EnvOptions {
+    fn default() -> Self {
+        Self {
+            list_delimiter: ',',
+            kv_delimiter: '=',
+        }
+    }
+}
+
+/// `from_vars` used to build a map deserializer from given environment variables
+pub(crate) fn from_vars<'de>(
+    vars: Vec<(String, String)>,
+    opts: EnvOptions,
+) -> MapDeserializer<'de, impl Iterator<Item = (String, Value)>, Error> {
+    MapDeserializer::new(vars.into_iter().map(move |(key, value)| {
+        (
+            key.to_lowercase(),
+            Value {
+                key,
+                input: value,
+                opts,
+            },
+        )
+    }))
+}
+
+/// `unused_key` used to format an ignored key path reported by `serde_ignored`, the top level key
+/// will be restored to its original environment variable name
+pub(crate) fn unused_key(path: String) -> String {
+    match path.split_once('.') {
+        Some((key, nested)) => format!("{}.{}", key.to_uppercase(), nested),
+        None => path.to_uppercase(),
+    }
+}
+
+/// `Value` is a deserializer of a single environment variable value
+pub(crate) struct Value {
+    key: String,
+    input: String,
+    opts: EnvOptions,
+}
+
+impl Value {
+    fn is_json(&self) -> bool {
+        let input = self.input.trim_start();
+        input.starts_with('{') || input.starts_with('[')
+    }
+
+    fn json(&self) -> Result<serde_json::Value, Error> {
+        serde_json::from_str(&self.input).map_err(|err| {
+            de::Error::custom(format!(
+                "{} while parsing value '{}' provided by {}",
+                err, self.input, self.key
+            ))
+        })
+    }
+
+    fn parse<T>(&self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: ToString,
+    {
+        self.input.trim().parse::<T>().map_err(|err| {
+            de::Error::custom(format!(
+                "{} while parsing value '{}' provided by {}",
+                err.to_string(),
+                self.input,
+                self.key
+            ))
+        })
+    }
+
+    fn nested(&self, input: &str) -> Self {
+        Self {
+            key: self.key.clone(),
+            input: input.trim().to_string(),
+            opts: self.opts,
+        }
+    }
+
+    fn items(&self) -> Vec<Self> {
+        if self.input.trim().is_empty() {
+            return Vec::new();
+        }
+
+        self.input
+            .split(self.opts.list_delimiter)
+            .map(|item| self.nested(item))
+            .collect()
+    }
+
+    fn entries(&self) -> Result<Vec<(Self, Self)>, Error> {
+        self.items()
+            .into_iter()
+            .map(|entry| {
+                entry
+                    .input
+                    .split_once(self.opts.kv_delimiter)
+                    .map(|(key, value)| (self.nested(key), self.nested(value)))
+                    .ok_or(de::Error::custom(format!(
+                        "invalid map entry '{}' provided by {}",
+                        entry.input, self.key
+                    )))
+            })
+            .collect()
+    }
+}
+
+impl<'de> IntoDeserializer<'de, Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self::Deserializer {
+        self
+    }
+}
+
+macro_rules! deserialize_parsed_values {
+    ($($ty:ident => $method:ident => $visit:ident,)*) => {
+        $(
+            fn $method<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+            where
+                V: Visitor<'de>,
+            {
+                visitor.$visit(self.parse::<$ty>()?)
+            }
+        )*
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.input)
+    }
+
+    /// String values will never be parsed as JSON, even when they start with `{` or `[`
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.input)
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.input)
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.is_json() {
+            return self
+                .json()?
+                .deserialize_seq(visitor)
+                .map_err(de::Error::custom);
+        }
+
+        SeqDeserializer::new(self.items().into_iter()).deserialize_any(visitor)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, visitor: V) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.is_json() {
+            return self
+                .json()?
+                .deserialize_map(visitor)
+                .map_err(de::Error::custom);
+        }
+
+        MapDeserializer::new(self.entries()?.into_iter()).deserialize_any(visitor)
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.is_json() {
+            return self
+                .json()?
+                .deserialize_enum(name, variants, visitor)
+                .map_err(de::Error::custom);
+        }
+
+        let input: StringDeserializer<Error> = self.input.into_deserializer();
+        visitor.visit_enum(input)
+    }
+
+    deserialize_parsed_values! {
+        bool => deserialize_bool => visit_bool,
+        u8 => deserialize_u8 => visit_u8,
+        u16 => deserialize_u16 => visit_u16,
+        u32 => deserialize_u32 => visit_u32,
+        u64 => deserialize_u64 => visit_u64,
+        u128 => deserialize_u128 => visit_u128,
+        i8 => deserialize_i8 => visit_i8,
+        i16 => deserialize_i16 => visit_i16,
+        i32 => deserialize_i32 => visit_i32,
+        i64 => deserialize_i64 => visit_i64,
+        i128 => deserialize_i128 => visit_i128,
+        f32 => deserialize_f32 => visit_f32,
+        f64 => deserialize_f64 => visit_f64,
+        char => deserialize_char => visit_char,
+    }
+
+    forward_to_deserialize_any! {
+        bytes byte_buf unit unit_struct identifier ignored_any
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    use rst_common::standard::serde::{self, Deserialize};
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "self::serde")]
+    struct Limits {
+        a: u32,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "self::serde")]
+    struct Config {
+        hosts: Vec<String>,
+        ports: Vec<u16>,
+        labels: HashMap<String, String>,
+        limits: Limits,
+        matrix: Vec<Vec<u32>>,
+        debug: Option<bool>,
+    }
+
+    fn vars() -> Vec<(String, String)> {
+        vec![
+            ("HOSTS".to_string(), "a, b,c".to_string()),
+            ("PORTS".to_string(), "8080;8081".to_string()),
+            ("LABELS".to_string(), "team=core;tier=web".to_string()),
+            ("LIMITS".to_string(), r#"{"a": 1}"#.to_string()),
+            ("MATRIX".to_string(), "[[1, 2], [3]]".to_string()),
+        ]
+    }
+
+    #[test]
+    fn test_deserialize_complex_values() {
+        let opts = EnvOptions {
+            list_delimiter: ';',
+            ..EnvOptions::default()
+        };
+
+        let mut input = vars();
+        input[0].1 = "a; b;c".to_string();
+
+        let cfg = Config::deserialize(from_vars(input, opts));
+        assert!(cfg.is_ok());
+
+        let cfg = cfg.unwrap();
+        assert_eq!(cfg.hosts, vec!["a", "b", "c"]);
+        assert_eq!(cfg.ports, vec![8080, 8081]);
+        assert_eq!(cfg.labels.get("team").unwrap(), "core");
+        assert_eq!(cfg.labels.get("tier").unwrap(), "web");
+        assert_eq!(cfg.limits, Limits { a: 1 });
+        assert_eq!(cfg.matrix, vec![vec![1, 2], vec![3]]);
+        assert_eq!(cfg.debug, None)
+    }
+
+    #[test]
+    fn test_deserialize_default_delimiter() {
+        let mut input = vars();
+        input[1].1 = "8080,8081".to_string();
+        input[2].1 = "team=core,tier=web".to_string();
+        input.push(("DEBUG".to_string(), "true".to_string()));
+
+        let cfg = Config::deserialize(from_vars(input, EnvOptions::default()));
+        assert!(cfg.is_ok());
+
+        let cfg = cfg.unwrap();
+        assert_eq!(cfg.hosts, vec!["a", "b", "c"]);
+        assert_eq!(cfg.ports, vec![8080, 8081]);
+        assert_eq!(cfg.labels.len(), 2);
+        assert_eq!(cfg.debug, Some(true))
+    }
+
+    #[test]
+    fn test_deserialize_invalid_map_entry() {
+        let mut input = vars();
+        input[1].1 = "8080,8081".to_string();
+        input[2].1 = "team".to_string();
+
+        let cfg = Config::deserialize(from_vars(input, EnvOptions::default()));
+        assert!(cfg.is_err());
+        assert!(cfg
+            .unwrap_err()
+            .to_string()
+            .contains("invalid map entry 'team' provided by LABELS"))
+    }
+
+    #[test]
+    fn test_deserialize_invalid_number() {
+        let mut input = vars();
+        input[1].1 = "8080,abc".to_string();
+
+        let cfg = Config::deserialize(from_vars(input, EnvOptions::default()));
+        assert!(cfg.is_err());
+        assert!(cfg
+            .unwrap_err()
+            .to_string()
+            .contains("while parsing value 'abc' provided by PORTS"))
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    #[serde(crate = "self::serde")]
+    struct Raw {
+        payload: String,
+        pattern: String,
+    }
+
+    #[test]
+    fn test_deserialize_json_like_string() {
+        let input = vec![
+            ("PAYLOAD".to_string(), r#"{"a":1}"#.to_string()),
+            ("PATTERN".to_string(), "[abc]".to_string()),
+        ];
+
+        let raw = Raw::deserialize(from_vars(input, EnvOptions::default()));
+        assert_eq!(
+            raw.unwrap(),
+            Raw {
+                payload: r#"{"a":1}"#.to_string(),
+                pattern: "[abc]".to_string(),
+            }
+        )
+    }
+
+    #[test]
+    fn test_unused_key() {
+        assert_eq!(unused_key("max_con".to_string()), "MAX_CON");
+        assert_eq!(unused_key("limits.b".to_string()), "LIMITS.b")
+    }
+}
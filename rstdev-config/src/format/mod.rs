@@ -7,24 +7,94 @@
 //! - Environment Variables
 //!
 //! All given config source MUST BE implement `serde::de::DeserializeOwned`
-use envy;
+//!
+//! The environment variables parser also support list, map and inline JSON values, which can be
+//! configured through [`EnvOptions`] and [`use_env_with`]
+//!
+//! All of these parsers also report their unused keys, which will be checked when the
+//! source parsed using [`crate::types::StrictMode`], and resolve all declared
+//! [`crate::types::KeyAlias`] before deserialized into its output type
+mod env;
+
+use std::path::Path;
+
+use serde_ignored;
 use serde_yaml;
 use toml;
 
-use rst_common::standard::serde::de::DeserializeOwned;
+use rst_common::standard::serde::de::{DeserializeOwned, Deserializer};
 use rst_common::standard::serde_json;
 
+use crate::alias;
+use crate::strict;
 use crate::types::ConfigError;
 
+pub use env::EnvOptions;
+
+/// `FileFormat` is a list of supported configuration file formats
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FileFormat {
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl FileFormat {
+    pub fn all() -> [FileFormat; 3] {
+        [FileFormat::Toml, FileFormat::Yaml, FileFormat::Json]
+    }
+
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            FileFormat::Toml => &["toml"],
+            FileFormat::Yaml => &["yaml", "yml"],
+            FileFormat::Json => &["json"],
+        }
+    }
+
+    /// `from_path` used to detect a file format based on its file extension
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?.to_lowercase();
+        FileFormat::all()
+            .into_iter()
+            .find(|format| format.extensions().contains(&ext.as_str()))
+    }
+}
+
+/// `use_format` used to build a parser based on given [`FileFormat`], it will be useful
+/// when the file format only known at runtime, like a discovered configuration file
+pub fn use_format<In, Out>(format: FileFormat) -> impl FnOnce(In) -> Result<Out, ConfigError>
+where
+    In: ToString,
+    Out: DeserializeOwned,
+{
+    move |input| match format {
+        FileFormat::Toml => use_toml(input),
+        FileFormat::Yaml => use_yaml(input),
+        FileFormat::Json => use_json(input),
+    }
+}
+
 pub fn use_toml<In, Out>(input: In) -> Result<Out, ConfigError>
 where
     In: ToString,
     Out: DeserializeOwned,
 {
-    let from_toml = toml::from_str(&input.to_string())
-        .map_err(|err| ConfigError::FormatError(err.to_string()))?;
+    let content = input.to_string();
+    if alias::is_enabled() {
+        let tree: toml::Table =
+            toml::from_str(&content).map_err(|err| ConfigError::FormatError(err.to_string()))?;
+
+        // a datetime value is only kept by the `toml` text deserializer, so the resolved tree
+        // will be rendered back before deserialized
+        let resolved = alias::resolve(toml::Value::Table(tree))?;
+        let content =
+            toml::to_string(&resolved).map_err(|err| ConfigError::FormatError(err.to_string()))?;
 
-    Ok(from_toml)
+        return deserialize(toml::Deserializer::new(&content));
+    }
+
+    deserialize(toml::Deserializer::new(&content))
 }
 
 pub fn use_yaml<In, Out>(input: In) -> Result<Out, ConfigError>
@@ -32,10 +102,15 @@ where
     In: ToString,
     Out: DeserializeOwned,
 {
-    let from_yaml = serde_yaml::from_str(&input.to_string())
-        .map_err(|err| ConfigError::FormatError(err.to_string()))?;
+    let content = input.to_string();
+    if alias::is_enabled() {
+        let tree: serde_yaml::Value = serde_yaml::from_str(&content)
+            .map_err(|err| ConfigError::FormatError(err.to_string()))?;
+
+        return deserialize(alias::resolve(tree)?);
+    }
 
-    Ok(from_yaml)
+    deserialize(serde_yaml::Deserializer::from_str(&content))
 }
 
 pub fn use_json<In, Out>(input: In) -> Result<Out, ConfigError>
@@ -43,7 +118,19 @@ where
     In: ToString,
     Out: DeserializeOwned,
 {
-    let from_json = serde_json::from_str(&input.to_string())
+    let content = input.to_string();
+    if alias::is_enabled() {
+        let tree: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|err| ConfigError::FormatError(err.to_string()))?;
+
+        return deserialize(alias::resolve(tree)?);
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_str(&content);
+    let from_json = deserialize(&mut deserializer)?;
+
+    deserializer
+        .end()
         .map_err(|err| ConfigError::FormatError(err.to_string()))?;
 
     Ok(from_json)
@@ -54,7 +141,45 @@ where
     In: IntoIterator<Item = (String, String)>,
     Out: DeserializeOwned,
 {
-    let from_env =
-        envy::from_iter(input).map_err(|err| ConfigError::FormatError(err.to_string()))?;
-    Ok(from_env)
+    use_env_with(EnvOptions::default())(input)
+}
+
+/// `use_env_with` used to build environment variables parser with custom [`EnvOptions`]
+///
+/// Example:
+///
+/// ```rust
+/// let opts = EnvOptions {
+///     list_delimiter: ';',
+///     ..EnvOptions::default()
+/// };
+///
+/// let cfg: Config = Builder::new(from_env("APP_".to_string()))
+///     .fetch()?
+///     .parse(use_env_with(opts))?;
+/// ```
+pub fn use_env_with<In, Out>(opts: EnvOptions) -> impl FnOnce(In) -> Result<Out, ConfigError>
+where
+    In: IntoIterator<Item = (String, String)>,
+    Out: DeserializeOwned,
+{
+    move |input| {
+        let vars = alias::resolve_vars(input.into_iter().collect())?;
+        let from_env = serde_ignored::deserialize(env::from_vars(vars, opts), |path| {
+            strict::report(env::unused_key(path.to_string()))
+        })
+        .map_err(|err| ConfigError::FormatError(err.to_string()))?;
+
+        Ok(from_env)
+    }
+}
+
+/// `deserialize` used to deserialize given input while reporting all of its unused keys
+fn deserialize<'de, D, Out>(deserializer: D) -> Result<Out, ConfigError>
+where
+    D: Deserializer<'de>,
+    Out: DeserializeOwned,
+{
+    serde_ignored::deserialize(deserializer, |path| strict::report(path.to_string()))
+        .map_err(|err| ConfigError::FormatError(err.to_string()))
 }
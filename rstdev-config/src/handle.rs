@@ -0,0 +1,332 @@
+//! A `handle` module provides [`Handle`] which is a shared object used to hold the current
+//! typed configuration
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock, PoisonError};
+
+use arc_swap::ArcSwap;
+use rst_common::standard::serde::Serialize;
+
+use crate::diff::{diff_values, Change};
+use crate::types::ConfigError;
+
+type Subscriber<T> = Arc<dyn Fn(&T, &T) + Send + Sync>;
+
+static GLOBALS: OnceLock<Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>> = OnceLock::new();
+
+/// `Handle` used to share a typed configuration between components
+///
+/// Each [`Handle::snapshot`] is a cheap and lock-free operation which give the current
+/// configuration value. When the configuration reloaded, the value will be replaced atomically,
+/// a component that still hold an old snapshot will keep using its old value until it takes
+/// a new snapshot.
+///
+/// This object can be injected to any components by cloning it, all of the clones will share
+/// the same value. It also can be registered as a global handle using [`Handle::set_global`].
+///
+/// For a configuration type that implement `Serialize`, use [`Handle::subscribe_prefix`] to only
+/// get notified when there are changes under specific key prefix.
+///
+/// Example:
+///
+/// ```rust
+/// let cfg: Config = Builder::new(from_file(path.clone())).fetch()?.parse(use_toml)?;
+/// let handle = Handle::new(cfg);
+/// handle.subscribe(|old, new| println!("reloaded: {:?} -> {:?}", old, new));
+///
+/// handle.reload(|| Builder::new(from_file(path)).fetch()?.parse(use_toml))?;
+/// let current = handle.snapshot();
+/// ```
+pub struct Handle<T> {
+    current: Arc<ArcSwap<T>>,
+    subscribers: Arc<Mutex<Vec<Subscriber<T>>>>,
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Self {
+            current: self.current.clone(),
+            subscribers: self.subscribers.clone(),
+        }
+    }
+}
+
+impl<T> Handle<T> {
+    pub fn new(value: T) -> Self {
+        Self {
+            current: Arc::new(ArcSwap::from_pointee(value)),
+            subscribers: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+
+    /// `snapshot` used to get the current configuration value
+    pub fn snapshot(&self) -> Arc<T> {
+        self.current.load_full()
+    }
+
+    /// `store` used to replace the current configuration value atomically, all subscribers
+    /// will be notified with the old and new values
+    pub fn store(&self, value: T) -> Arc<T> {
+        self.swap(Arc::new(value))
+    }
+
+    /// `reload` used to load a new configuration value, the current value only
+    /// replaced when the loader succeed
+    pub fn reload<F>(&self, loader: F) -> Result<Arc<T>, ConfigError>
+    where
+        F: FnOnce() -> Result<T, ConfigError>,
+    {
+        let value = Arc::new(loader()?);
+        self.swap(value.clone());
+        Ok(value)
+    }
+
+    /// `subscribe` used to register a callback that will be called each time the
+    /// configuration value replaced
+    ///
+    /// The callbacks are called without holding any locks, so a callback may take a snapshot,
+    /// subscribe or store a new value. When multiple values stored concurrently, each callback
+    /// will receive the exact old and new values of each swap, but not in a guaranteed order
+    pub fn subscribe<F>(&self, cb: F)
+    where
+        F: Fn(&T, &T) + Send + Sync + 'static,
+    {
+        self.subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(cb));
+    }
+
+    fn swap(&self, new: Arc<T>) -> Arc<T> {
+        let old = self.current.swap(new.clone());
+        let subscribers: Vec<Subscriber<T>> = self
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+
+        for subscriber in subscribers.iter() {
+            subscriber(&old, &new);
+        }
+
+        old
+    }
+}
+
+impl<T> Handle<T>
+where
+    T: Serialize,
+{
+    /// `subscribe_prefix` used to register a callback that will only be called when there are
+    /// changes under given key prefix, example: `log` will be notified for `log.level` changes
+    ///
+    /// The callback will receive all changes under the prefix including the old and new values
+    pub fn subscribe_prefix<F>(&self, prefix: String, cb: F)
+    where
+        F: Fn(&[Change], &T, &T) + Send + Sync + 'static,
+    {
+        self.subscribe(move |old, new| {
+            let changes: Vec<Change> = match diff_values(old, new) {
+                Ok(changes) => changes
+                    .into_iter()
+                    .filter(|change| change.is_under(&prefix))
+                    .collect(),
+                Err(_) => return,
+            };
+
+            if !changes.is_empty() {
+                cb(&changes, old, new);
+            }
+        })
+    }
+}
+
+impl<T> Handle<T>
+where
+    T: Send + Sync + 'static,
+{
+    /// `set_global` used to register this handle as a global handle of its configuration type
+    ///
+    /// It will replace previous global handle of the same type if exists
+    pub fn set_global(&self) {
+        GLOBALS
+            .get_or_init(|| Mutex::new(HashMap::new()))
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(TypeId::of::<T>(), Box::new(self.clone()));
+    }
+
+    /// `global` used to get registered global handle of its configuration type
+    pub fn global() -> Option<Self> {
+        GLOBALS
+            .get()?
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(&TypeId::of::<T>())
+            .and_then(|handle| handle.downcast_ref::<Self>())
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use rst_common::standard::serde::{self, Serialize};
+
+    #[derive(Debug, Clone, PartialEq, Serialize)]
+    #[serde(crate = "self::serde")]
+    struct Config {
+        level: String,
+    }
+
+    fn config(level: &str) -> Config {
+        Config {
+            level: level.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_snapshot_and_store() {
+        let handle = Handle::new(config("info"));
+        let snapshot = handle.snapshot();
+
+        let old = handle.store(config("debug"));
+        assert_eq!(old.level, "info");
+        assert_eq!(snapshot.level, "info");
+        assert_eq!(handle.snapshot().level, "debug")
+    }
+
+    #[test]
+    fn test_shared_between_clones() {
+        let handle = Handle::new(config("info"));
+        let cloned = handle.clone();
+
+        cloned.store(config("debug"));
+        assert_eq!(handle.snapshot().level, "debug")
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let handle = Handle::new(config("info"));
+        let counter = Arc::new(AtomicUsize::new(0));
+        let counter_cloned = counter.clone();
+
+        handle.subscribe(move |old, new| {
+            assert_eq!(old.level, "info");
+            assert_eq!(new.level, "debug");
+            counter_cloned.fetch_add(1, Ordering::SeqCst);
+        });
+
+        handle.store(config("debug"));
+        assert_eq!(counter.load(Ordering::SeqCst), 1)
+    }
+
+    #[test]
+    fn test_subscribe_reentrant() {
+        let handle = Handle::new(config("info"));
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let cloned = handle.clone();
+        let counter_cloned = counter.clone();
+        handle.subscribe(move |_, new| {
+            if new.level == "debug" {
+                cloned.subscribe(|_, _| {});
+                cloned.store(config("trace"));
+            }
+
+            counter_cloned.fetch_add(1, Ordering::SeqCst);
+        });
+
+        handle.store(config("debug"));
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+        assert_eq!(handle.snapshot().level, "trace")
+    }
+
+    #[test]
+    fn test_subscribe_poisoned() {
+        let handle = Handle::new(config("info"));
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let subscribers = handle.subscribers.clone();
+        let poisoned = std::thread::spawn(move || {
+            let _guard = subscribers.lock().unwrap();
+            panic!("poison the subscribers")
+        })
+        .join();
+        assert!(poisoned.is_err());
+
+        let counter_cloned = counter.clone();
+        handle.subscribe(move |_, _| {
+            counter_cloned.fetch_add(1, Ordering::SeqCst);
+        });
+
+        handle.store(config("debug"));
+        assert_eq!(counter.load(Ordering::SeqCst), 1)
+    }
+
+    #[test]
+    fn test_subscribe_prefix() {
+        let handle = Handle::new(config("info"));
+        let level_counter = Arc::new(AtomicUsize::new(0));
+        let db_counter = Arc::new(AtomicUsize::new(0));
+
+        let level_counter_cloned = level_counter.clone();
+        handle.subscribe_prefix("level".to_string(), move |changes, _, new| {
+            assert_eq!(changes.len(), 1);
+            assert_eq!(changes[0].path(), "level");
+            assert_eq!(new.level, "debug");
+            level_counter_cloned.fetch_add(1, Ordering::SeqCst);
+        });
+
+        let db_counter_cloned = db_counter.clone();
+        handle.subscribe_prefix("db".to_string(), move |_, _, _| {
+            db_counter_cloned.fetch_add(1, Ordering::SeqCst);
+        });
+
+        handle.store(config("debug"));
+        handle.store(config("debug"));
+        assert_eq!(level_counter.load(Ordering::SeqCst), 1);
+        assert_eq!(db_counter.load(Ordering::SeqCst), 0)
+    }
+
+    #[test]
+    fn test_reload_failed() {
+        let handle = Handle::new(config("info"));
+        let result =
+            handle.reload(|| Err(ConfigError::ParseError("unable to read file".to_string())));
+
+        assert!(result.is_err());
+        assert_eq!(handle.snapshot().level, "info")
+    }
+
+    #[test]
+    fn test_reload_success() {
+        let handle = Handle::new(config("info"));
+        let result = handle.reload(|| Ok(config("debug")));
+
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap().level, "debug");
+        assert_eq!(handle.snapshot().level, "debug")
+    }
+
+    #[test]
+    fn test_global() {
+        #[derive(Debug)]
+        struct GlobalConfig {
+            level: String,
+        }
+
+        assert!(Handle::<GlobalConfig>::global().is_none());
+
+        let handle = Handle::new(GlobalConfig {
+            level: "info".to_string(),
+        });
+        handle.set_global();
+
+        let global = Handle::<GlobalConfig>::global();
+        assert!(global.is_some());
+        assert_eq!(global.unwrap().snapshot().level, "info")
+    }
+}
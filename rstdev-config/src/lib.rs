@@ -1,12 +1,21 @@
 #![doc = include_str!("../README.md")]
 
+pub mod diff;
+pub mod flags;
 pub mod format;
 pub mod parser;
 pub mod types;
 pub mod values;
 
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+
+mod alias;
 mod builder;
+mod handle;
 mod source;
+mod strict;
 
 pub use builder::Builder;
+pub use handle::Handle;
 pub use source::Source;
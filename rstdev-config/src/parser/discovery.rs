@@ -0,0 +1,203 @@
+use std::env;
+use std::path::PathBuf;
+
+use crate::format::FileFormat;
+use crate::types::ConfigError;
+
+/// `Discovery` used to search a configuration file named `<app>.{toml,yaml,yml,json}` from
+/// an ordered list of directories
+///
+/// By default, the directories are:
+///
+/// - Current working directory
+/// - `$<APP>_CONFIG_DIR`, an override directory, example: `$MY_APP_CONFIG_DIR` for `my-app`
+/// - `$XDG_CONFIG_HOME/<app>` (or `$HOME/.config/<app>`)
+/// - `$XDG_CONFIG_DIRS/<app>` (or `/etc/xdg/<app>`)
+/// - `/etc/<app>`
+///
+/// The first file found will be chosen, so a file in the current working directory will be
+/// chosen before any files in the override directory
+pub struct Discovery {
+    app: String,
+    paths: Vec<PathBuf>,
+}
+
+/// `Discovered` is a chosen configuration file including for all paths that
+/// already tried before the file found
+#[derive(Debug, Clone, PartialEq)]
+pub struct Discovered {
+    pub path: PathBuf,
+    pub format: FileFormat,
+    pub tried: Vec<PathBuf>,
+}
+
+impl Discovery {
+    pub fn new(app: String) -> Self {
+        let paths = default_paths(&app);
+        Self { app, paths }
+    }
+
+    /// `with_paths` used to replace the default search directories
+    pub fn with_paths(mut self, paths: Vec<PathBuf>) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// `add_path` used to add a search directory with the lowest priority
+    pub fn add_path(mut self, path: PathBuf) -> Self {
+        self.paths.push(path);
+        self
+    }
+
+    pub fn paths(&self) -> &[PathBuf] {
+        &self.paths
+    }
+
+    pub fn find(&self) -> Result<Discovered, ConfigError> {
+        let mut tried = Vec::new();
+
+        for dir in self.paths.iter() {
+            for format in FileFormat::all() {
+                for ext in format.extensions() {
+                    let path = dir.join(format!("{}.{}", self.app, ext));
+                    tried.push(path.clone());
+
+                    if path.is_file() {
+                        return Ok(Discovered {
+                            path,
+                            format,
+                            tried,
+                        });
+                    }
+                }
+            }
+        }
+
+        let tried_paths: Vec<String> = tried
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect();
+
+        Err(ConfigError::DiscoveryError(format!(
+            "no configuration file found for `{}`, tried: {}",
+            self.app,
+            tried_paths.join(", ")
+        )))
+    }
+}
+
+fn default_paths(app: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+
+    if let Ok(dir) = env::current_dir() {
+        paths.push(dir);
+    }
+
+    let override_var = format!("{}_CONFIG_DIR", app.to_uppercase().replace('-', "_"));
+    if let Ok(dir) = env::var(override_var) {
+        paths.push(PathBuf::from(dir));
+    }
+
+    let config_home = env::var("XDG_CONFIG_HOME")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| {
+            env::var("HOME")
+                .ok()
+                .map(|home| PathBuf::from(home).join(".config"))
+        });
+
+    if let Some(dir) = config_home {
+        paths.push(dir.join(app));
+    }
+
+    let config_dirs = env::var("XDG_CONFIG_DIRS")
+        .ok()
+        .filter(|dirs| !dirs.is_empty())
+        .unwrap_or_else(|| "/etc/xdg".to_string());
+
+    for dir in config_dirs.split(':').filter(|dir| !dir.is_empty()) {
+        paths.push(PathBuf::from(dir).join(app));
+    }
+
+    paths.push(PathBuf::from("/etc").join(app));
+    paths
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::testing::{EnvGuard, TempFile};
+
+    fn fixtures_dir() -> PathBuf {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("fixtures");
+        path
+    }
+
+    #[test]
+    fn test_find() {
+        let missing = fixtures_dir().join("missing");
+        let discovery =
+            Discovery::new("test".to_string()).with_paths(vec![missing.clone(), fixtures_dir()]);
+
+        let found = discovery.find();
+        assert!(found.is_ok());
+
+        let found = found.unwrap();
+        assert_eq!(found.path, fixtures_dir().join("test.toml"));
+        assert_eq!(found.format, FileFormat::Toml);
+        assert_eq!(found.tried.len(), 5);
+        assert_eq!(found.tried[0], missing.join("test.toml"))
+    }
+
+    #[test]
+    fn test_find_override_dir() {
+        let file = TempFile::new("json", "{}").unwrap();
+        let app = file
+            .path()
+            .file_stem()
+            .unwrap()
+            .to_string_lossy()
+            .to_string();
+        let dir = file.path().parent().unwrap().to_path_buf();
+
+        let mut guard = EnvGuard::new();
+        guard.set(
+            &format!("{}_CONFIG_DIR", app.to_uppercase().replace('-', "_")),
+            &dir.display().to_string(),
+        );
+
+        let discovery = Discovery::new(app);
+        assert_eq!(discovery.paths()[0], env::current_dir().unwrap());
+        assert_eq!(discovery.paths()[1], dir);
+
+        let found = discovery.find();
+        assert!(found.is_ok());
+
+        let found = found.unwrap();
+        assert_eq!(found.path, file.path());
+        assert_eq!(found.format, FileFormat::Json)
+    }
+
+    #[test]
+    fn test_find_not_found() {
+        let missing = fixtures_dir().join("missing");
+        let discovery = Discovery::new("test".to_string()).with_paths(vec![missing.clone()]);
+
+        let found = discovery.find();
+        assert!(found.is_err());
+        assert_eq!(
+            found.unwrap_err(),
+            ConfigError::DiscoveryError(format!(
+                "no configuration file found for `test`, tried: {}, {}, {}, {}",
+                missing.join("test.toml").display(),
+                missing.join("test.yaml").display(),
+                missing.join("test.yml").display(),
+                missing.join("test.json").display()
+            ))
+        )
+    }
+}
@@ -1,6 +1,6 @@
 use std::env;
 
-use crate::types::{ConfigError, SourceParser};
+use crate::types::{ConfigError, EnvProvider, SourceParser};
 use crate::values::TupleValue;
 use crate::Source;
 
@@ -14,21 +14,43 @@ use crate::Source;
 /// The `PREFIX_` also used to prevent conflicted variable names between
 /// our system needs and with the external system, since we cannot control
 /// external variable names outside of our system.
+///
+/// By default, the variables will be read from the current process, use
+/// [`Env::with_provider`] to inject a custom [`EnvProvider`]
 pub struct Env {
     prefix: String,
+    provider: Box<dyn EnvProvider + Send + Sync>,
 }
 
 impl Env {
     pub fn new(prefix: String) -> Self {
-        Self { prefix }
+        Self {
+            prefix,
+            provider: Box::new(ProcessEnv),
+        }
+    }
+
+    /// `with_provider` used to replace the default process environment variables provider
+    pub fn with_provider(mut self, provider: impl EnvProvider + Send + Sync + 'static) -> Self {
+        self.provider = Box::new(provider);
+        self
+    }
+}
+
+/// `ProcessEnv` is a default [`EnvProvider`] that read all variables from the current process
+struct ProcessEnv;
+
+impl EnvProvider for ProcessEnv {
+    fn vars(&self) -> Vec<(String, String)> {
+        env::vars().collect()
     }
 }
 
 impl SourceParser<TupleValue, Vec<(String, String)>> for Env {
     fn fetch(&self) -> Result<Source<TupleValue, Vec<(String, String)>>, ConfigError> {
-        let vars = env::vars();
-        let input_vars: Vec<(String, String)> = vars.into_iter().collect();
-        let input_source: Vec<(String, String)> = input_vars
+        let input_source: Vec<(String, String)> = self
+            .provider
+            .vars()
             .into_iter()
             .filter(|(key, _)| key.starts_with(&self.prefix))
             .map(|(key, value)| (key.trim_start_matches(&self.prefix).to_owned(), value))
@@ -42,19 +64,26 @@ impl SourceParser<TupleValue, Vec<(String, String)>> for Env {
 
         Ok(Source::new(TupleValue::new(input_source)))
     }
+
+    fn name(&self) -> String {
+        format!("env:{}", self.prefix)
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::{collections::HashMap, env};
+    use std::collections::HashMap;
+
+    use crate::testing::{EnvGuard, MemoryEnv};
 
     #[test]
     fn test_fetch() {
-        env::set_var("TEST_KEY1", "value1");
-        env::set_var("TEST_KEY2", "value2");
+        let provider = MemoryEnv::new()
+            .with_var("TEST_KEY1", "value1")
+            .with_var("TEST_KEY2", "value2");
 
-        let parser = Env::new("TEST_".to_string());
+        let parser = Env::new("TEST_".to_string()).with_provider(provider);
         let source = parser.fetch();
         assert!(!source.is_err());
 
@@ -87,10 +116,11 @@ mod tests {
 
     #[test]
     fn test_fetch_no_vars() {
-        env::set_var("KEY1", "value1");
-        env::set_var("KEY2", "value2");
+        let provider = MemoryEnv::new()
+            .with_var("KEY1", "value1")
+            .with_var("KEY2", "value2");
 
-        let parser = Env::new("INVALID_".to_string());
+        let parser = Env::new("INVALID_".to_string()).with_provider(provider);
         let source = parser.fetch();
         assert!(source.is_err());
         assert!(matches!(
@@ -102,4 +132,19 @@ mod tests {
             ConfigError::FormatError("no environment variables available".to_string())
         )
     }
+
+    #[test]
+    fn test_fetch_process_env() {
+        let mut guard = EnvGuard::new();
+        guard.set("TEST_PROCESS_KEY", "value");
+
+        let source = Env::new("TEST_PROCESS_".to_string()).fetch();
+        assert!(source.is_ok());
+
+        let result = source.unwrap().parse(Ok);
+        assert_eq!(
+            result.unwrap(),
+            vec![("KEY".to_string(), "value".to_string())]
+        )
+    }
 }
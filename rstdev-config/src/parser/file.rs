@@ -1,4 +1,6 @@
 use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
 
 use crate::types::{ConfigError, SourceParser};
 use crate::values::StringValue;
@@ -6,21 +8,69 @@ use crate::Source;
 
 /// File is an adapter that will fetch the configuration as a string
 /// from a given input file path
+///
+/// A special `-` file path will read the configuration from the `stdin`
 pub struct File {
-    filepath: String,
+    filepath: PathBuf,
 }
 
 impl File {
-    pub fn new(filepath: String) -> Self {
-        Self { filepath }
+    pub fn new(filepath: impl AsRef<Path>) -> Self {
+        Self {
+            filepath: filepath.as_ref().to_path_buf(),
+        }
+    }
+
+    fn is_stdin(&self) -> bool {
+        self.filepath == Path::new("-")
     }
 }
 
 impl SourceParser<StringValue, String> for File {
     fn fetch(&self) -> Result<Source<StringValue, String>, ConfigError> {
-        let content = fs::read_to_string(self.filepath.clone())
-            .map_err(|err| ConfigError::ParseError(err.to_string()))?;
+        let content = if self.is_stdin() {
+            let mut content = String::new();
+            io::stdin()
+                .read_to_string(&mut content)
+                .map_err(|err| ConfigError::ParseError(err.to_string()))?;
+
+            content
+        } else {
+            fs::read_to_string(&self.filepath)
+                .map_err(|err| ConfigError::ParseError(err.to_string()))?
+        };
 
         Ok(Source::new(StringValue::new(content)))
     }
+
+    fn name(&self) -> String {
+        if self.is_stdin() {
+            return String::from("stdin");
+        }
+
+        self.filepath.display().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_path() {
+        let mut path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        path.push("fixtures/test.toml");
+
+        let parser = File::new(&path);
+        assert_eq!(parser.name(), path.display().to_string());
+
+        let content = parser.fetch().unwrap().parse(Ok);
+        assert_eq!(content.unwrap(), fs::read_to_string(&path).unwrap())
+    }
+
+    #[test]
+    fn test_stdin_name() {
+        assert_eq!(File::new("-").name(), "stdin");
+        assert_eq!(File::new(String::from("config.toml")).name(), "config.toml")
+    }
 }
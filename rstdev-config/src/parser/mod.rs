@@ -2,17 +2,44 @@
 //!
 //! We just prevent to re-export all source adapter implementer to simplify our `Builder`
 
+use std::io::Read;
+use std::path::Path;
+
+mod discovery;
 mod env;
 mod file;
+mod reader;
+mod storage;
+
+pub use discovery::{Discovered, Discovery};
+pub use storage::StorageSource;
 
 /// `from_file` used to build `File` adapter which is an adapter that read
-/// configuration from a physical file.
-pub fn from_file(filepath: String) -> file::File {
+/// configuration from a physical file, or from the `stdin` when given path is `-`
+pub fn from_file(filepath: impl AsRef<Path>) -> file::File {
     file::File::new(filepath)
 }
 
+/// `from_reader` used to build `Reader` adapter which is an adapter that read
+/// configuration from any [`Read`] implementer
+pub fn from_reader<R: Read>(reader: R) -> reader::Reader<R> {
+    reader::Reader::new(reader)
+}
+
+/// `from_embedded` used to build `Embedded` adapter which is an adapter that read
+/// configuration from a static string, example: `from_embedded(include_str!("default.toml"))`
+pub fn from_embedded(content: &'static str) -> reader::Embedded {
+    reader::Embedded::new(content)
+}
+
 /// `from_env` used to build `Env` adapter which is an adapter that read all
 /// environment variables
 pub fn from_env(prefix: String) -> env::Env {
     env::Env::new(prefix)
 }
+
+/// `discover` used to build [`Discovery`] which is an object used to search a configuration
+/// file named `<app>.{toml,yaml,yml,json}` from standard directories
+pub fn discover(app: String) -> Discovery {
+    Discovery::new(app)
+}
@@ -0,0 +1,126 @@
+use std::cell::RefCell;
+use std::io::Read;
+
+use crate::types::{ConfigError, SourceParser};
+use crate::values::StringValue;
+use crate::Source;
+
+/// Reader is an adapter that will fetch the configuration as a string
+/// from any given [`Read`] implementer, like a network stream or the `stdin`
+///
+/// The reader will be consumed on each fetch, so the next fetch will only get the
+/// remaining content
+pub struct Reader<R: Read> {
+    reader: RefCell<R>,
+    name: String,
+}
+
+impl<R: Read> Reader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader: RefCell::new(reader),
+            name: String::from("reader"),
+        }
+    }
+
+    /// `with_name` used to identify the reader when reporting unused configuration keys
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+}
+
+impl<R: Read> SourceParser<StringValue, String> for Reader<R> {
+    fn fetch(&self) -> Result<Source<StringValue, String>, ConfigError> {
+        let mut content = String::new();
+        self.reader
+            .borrow_mut()
+            .read_to_string(&mut content)
+            .map_err(|err| ConfigError::ParseError(err.to_string()))?;
+
+        Ok(Source::new(StringValue::new(content)))
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+/// Embedded is an adapter that will fetch the configuration from a static string,
+/// like a compiled-in default configuration using `include_str!`
+pub struct Embedded {
+    content: &'static str,
+}
+
+impl Embedded {
+    pub fn new(content: &'static str) -> Self {
+        Self { content }
+    }
+}
+
+impl SourceParser<StringValue, String> for Embedded {
+    fn fetch(&self) -> Result<Source<StringValue, String>, ConfigError> {
+        Ok(Source::new(StringValue::new(self.content.to_string())))
+    }
+
+    fn name(&self) -> String {
+        String::from("embedded")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    use rst_common::standard::serde::{self, Deserialize};
+
+    use crate::format::{use_json, use_toml};
+    use crate::Builder;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(crate = "self::serde")]
+    struct Message {
+        message: String,
+    }
+
+    #[test]
+    fn test_fetch_reader() {
+        let reader = Reader::new(Cursor::new(r#"{"message": "hello world"}"#));
+        assert_eq!(reader.name(), "reader");
+
+        let cfg: Result<Message, ConfigError> = Builder::new(reader)
+            .fetch()
+            .and_then(|source| source.parse(use_json));
+
+        assert!(cfg.is_ok());
+        assert_eq!(cfg.unwrap().message, "hello world")
+    }
+
+    #[test]
+    fn test_fetch_reader_consumed() {
+        let reader = Reader::new(Cursor::new("message = \"hello world\""));
+        assert!(reader.fetch().is_ok());
+
+        let content = reader.fetch().unwrap().parse(Ok);
+        assert_eq!(content.unwrap(), "")
+    }
+
+    #[test]
+    fn test_fetch_embedded() {
+        let embedded = Embedded::new(include_str!("../../fixtures/test.toml"));
+        assert_eq!(embedded.name(), "embedded");
+
+        let content = embedded.fetch().unwrap().parse(Ok);
+        assert_eq!(
+            content.unwrap(),
+            include_str!("../../fixtures/test.toml").to_string()
+        );
+
+        let cfg: Result<Message, ConfigError> = Embedded::new("message = \"hello world\"")
+            .fetch()
+            .and_then(|source| source.parse(use_toml));
+
+        assert_eq!(cfg.unwrap().message, "hello world")
+    }
+}
@@ -0,0 +1,203 @@
+use crate::types::{ConfigError, SourceParser};
+use crate::values::TupleValue;
+use crate::Source;
+
+#[cfg(feature = "storage-mysql")]
+use rstdev_storage::engine::sql::mysql::db::MysqlDB;
+
+#[cfg(feature = "storage-postgresql")]
+use rstdev_storage::engine::sql::postgresql::db::PostgresqlDB;
+
+#[cfg(feature = "storage-rocksdb")]
+use rstdev_storage::engine::rocksdb::{executor::Executor, types::Instruction, types::OutputOpts};
+
+/// `StorageSource` is an adapter that read the configuration as key and value rows
+/// from an external storage managed by `rstdev-storage`
+///
+/// Since the [`SourceParser::fetch`] is a synchronous operation, all rows will be loaded
+/// once when the adapter built, use one of its async constructors to load the rows:
+///
+/// - [`StorageSource::from_mysql`], read all rows from a `key` and `value` table using [`MysqlDB`]
+///   pool connection, or its single connection when the pool is not available
+/// - [`StorageSource::from_postgresql`], same as `from_mysql` but using [`PostgresqlDB`]
+/// - [`StorageSource::from_rocksdb`], read given keys from a column family using RocksDB [`Executor`]
+///
+/// The rows will be formatted as [`TupleValue`], so it can be parsed using `use_env`, the
+/// same as the environment variables.
+pub struct StorageSource {
+    name: String,
+    rows: Vec<(String, String)>,
+}
+
+impl StorageSource {
+    /// `new` used to build the adapter from loaded rows, it can be used when the rows
+    /// loaded from other storages
+    pub fn new(name: String, rows: Vec<(String, String)>) -> Self {
+        Self { name, rows }
+    }
+
+    /// `from_mysql` used to load all rows from given table, the table must have `key`
+    /// and `value` text columns
+    #[cfg(feature = "storage-mysql")]
+    pub async fn from_mysql(db: &mut MysqlDB, table: String) -> Result<Self, ConfigError> {
+        validate_table(&table)?;
+
+        let query = format!("SELECT `key`, `value` FROM {}", table);
+        let rows: Vec<(String, String)> = match (db.pool_conn.as_ref(), db.single_conn.as_mut()) {
+            (Some(pool), _) => sqlx::query_as(&query).fetch_all(pool).await,
+            (None, Some(conn)) => sqlx::query_as(&query).fetch_all(conn).await,
+            (None, None) => {
+                return Err(ConfigError::ParseError(
+                    "missing mysql connection".to_string(),
+                ))
+            }
+        }
+        .map_err(|err| ConfigError::ParseError(err.to_string()))?;
+
+        Ok(Self::new(format!("mysql:{}", table), rows))
+    }
+
+    /// `from_postgresql` used to load all rows from given table, the table must have `key`
+    /// and `value` text columns
+    #[cfg(feature = "storage-postgresql")]
+    pub async fn from_postgresql(
+        db: &mut PostgresqlDB,
+        table: String,
+    ) -> Result<Self, ConfigError> {
+        validate_table(&table)?;
+
+        let query = format!("SELECT \"key\", \"value\" FROM {}", table);
+        let rows: Vec<(String, String)> = match (db.pool_conn.as_ref(), db.single_conn.as_mut()) {
+            (Some(pool), _) => sqlx::query_as(&query).fetch_all(pool).await,
+            (None, Some(conn)) => sqlx::query_as(&query).fetch_all(conn).await,
+            (None, None) => {
+                return Err(ConfigError::ParseError(
+                    "missing postgresql connection".to_string(),
+                ))
+            }
+        }
+        .map_err(|err| ConfigError::ParseError(err.to_string()))?;
+
+        Ok(Self::new(format!("postgresql:{}", table), rows))
+    }
+
+    /// `from_rocksdb` used to load given keys from the executor's column family, a missing key
+    /// will be skipped and all values must be a valid UTF-8 string
+    #[cfg(feature = "storage-rocksdb")]
+    pub async fn from_rocksdb(executor: &Executor, keys: Vec<String>) -> Result<Self, ConfigError> {
+        let output = executor
+            .exec(Instruction::MultiGetCf { keys: keys.clone() })
+            .await
+            .map_err(|err| ConfigError::ParseError(err.to_string()))?;
+
+        let values = match output {
+            OutputOpts::MultiBytes { values } => values,
+            _ => {
+                return Err(ConfigError::ParseError(
+                    "unexpected rocksdb output".to_string(),
+                ))
+            }
+        };
+
+        let mut rows = Vec::new();
+        for (key, value) in keys.into_iter().zip(values) {
+            let value = match value.map_err(|err| ConfigError::ParseError(err.to_string()))? {
+                Some(value) => value,
+                None => continue,
+            };
+
+            let value = String::from_utf8(value).map_err(|err| {
+                ConfigError::FormatError(format!("invalid value of `{}`: {}", key, err))
+            })?;
+
+            rows.push((key, value));
+        }
+
+        Ok(Self::new("rocksdb".to_string(), rows))
+    }
+}
+
+impl SourceParser<TupleValue, Vec<(String, String)>> for StorageSource {
+    fn fetch(&self) -> Result<Source<TupleValue, Vec<(String, String)>>, ConfigError> {
+        Ok(Source::new(TupleValue::new(self.rows.clone())))
+    }
+
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+/// `validate_table` used to make sure the table name is a plain identifier, since it cannot
+/// be bound as a query parameter
+#[cfg_attr(
+    not(any(feature = "storage-mysql", feature = "storage-postgresql")),
+    allow(dead_code)
+)]
+fn validate_table(table: &str) -> Result<(), ConfigError> {
+    let valid = !table.is_empty()
+        && table
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.');
+
+    if !valid {
+        return Err(ConfigError::FormatError(format!(
+            "invalid settings table name: {}",
+            table
+        )));
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rst_common::standard::serde::{self, Deserialize};
+
+    use crate::format::use_env;
+    use crate::Builder;
+
+    #[derive(Debug, Deserialize)]
+    #[serde(crate = "self::serde")]
+    struct Settings {
+        maintenance: bool,
+        max_upload: u32,
+    }
+
+    #[test]
+    fn test_fetch() {
+        let adapter = StorageSource::new(
+            "mysql:settings".to_string(),
+            vec![
+                ("MAINTENANCE".to_string(), "true".to_string()),
+                ("max_upload".to_string(), "10".to_string()),
+            ],
+        );
+
+        assert_eq!(adapter.name(), "mysql:settings");
+
+        let settings: Result<Settings, ConfigError> = Builder::new(adapter)
+            .fetch()
+            .and_then(|source| source.parse(use_env));
+
+        assert!(settings.is_ok());
+
+        let settings = settings.unwrap();
+        assert!(settings.maintenance);
+        assert_eq!(settings.max_upload, 10)
+    }
+
+    #[test]
+    fn test_validate_table() {
+        assert!(validate_table("settings").is_ok());
+        assert!(validate_table("app.settings").is_ok());
+        assert!(validate_table("").is_err());
+        assert_eq!(
+            validate_table("settings; DROP TABLE users").unwrap_err(),
+            ConfigError::FormatError(
+                "invalid settings table name: settings; DROP TABLE users".to_string()
+            )
+        )
+    }
+}
@@ -2,7 +2,9 @@ use std::marker::PhantomData;
 
 use rst_common::standard::serde::de::DeserializeOwned;
 
-use crate::types::{ConfigError, SourceFormatter};
+use crate::alias;
+use crate::strict;
+use crate::types::{ConfigError, Deprecation, KeyAlias, SourceFormatter, StrictMode, UnusedKey};
 
 /// Format is a object wrapper of given input as a string value
 ///
@@ -16,12 +18,21 @@ use crate::types::{ConfigError, SourceFormatter};
 /// its specific type parser.
 ///
 /// By using this approach, user will be able to create a custom source type parser based on their needs
+///
+/// A source may also be parsed in a [`StrictMode`], which will check all keys that not consumed
+/// by any fields from the given output type. Any declared [`KeyAlias`] will be resolved
+/// before the source deserialized into its output type
 #[derive(Debug)]
 pub struct Source<TFormatter, TValue>
 where
     TFormatter: for<'a> SourceFormatter<'a, TValue>,
 {
     input: TFormatter,
+    name: String,
+    strict: Option<StrictMode>,
+    aliases: Vec<KeyAlias>,
+    on_deprecation: fn(&Deprecation),
+    on_unused_key: fn(&UnusedKey),
     _phantomf: Option<PhantomData<TValue>>,
 }
 
@@ -33,16 +44,70 @@ where
     pub fn new(input: T) -> Self {
         Self {
             input,
+            name: String::from("unknown"),
+            strict: None,
+            aliases: Vec::new(),
+            on_deprecation: alias::ignore_deprecation,
+            on_unused_key: strict::ignore_unused_key,
             _phantomf: None,
         }
     }
 
+    /// `with_name` used to identify this source when reporting unused or deprecated keys
+    pub fn with_name(mut self, name: String) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// `with_strict` used to enable [`StrictMode`]
+    pub fn with_strict(mut self, mode: StrictMode) -> Self {
+        self.strict = Some(mode);
+        self
+    }
+
+    /// `with_aliases` used to declare all key aliases that will be resolved while parsing
+    pub fn with_aliases(mut self, aliases: Vec<KeyAlias>) -> Self {
+        self.aliases = aliases;
+        self
+    }
+
+    /// `on_deprecation` used to set the handler of each [`Deprecation`] warning, by default
+    /// all warnings will be ignored
+    pub fn on_deprecation(mut self, handler: fn(&Deprecation)) -> Self {
+        self.on_deprecation = handler;
+        self
+    }
+
+    /// `on_unused_key` used to set the handler of each [`UnusedKey`] warning emitted by
+    /// [`StrictMode::Warn`], by default all warnings will be ignored
+    pub fn on_unused_key(mut self, handler: fn(&UnusedKey)) -> Self {
+        self.on_unused_key = handler;
+        self
+    }
+
     pub fn parse<F, Out>(&self, cb: F) -> Result<Out, ConfigError>
     where
         F: FnOnce(St) -> Result<Out, ConfigError>,
         Out: DeserializeOwned,
     {
-        cb(self.input.get_source_value())
+        let input = self.input.get_source_value();
+        let ((out, deprecated), unused) =
+            strict::collect(|| alias::scope(self.aliases.clone(), || cb(input)));
+        let parsed = out?;
+
+        for alias in deprecated {
+            (self.on_deprecation)(&Deprecation {
+                source: self.name.clone(),
+                key: alias.from,
+                replacement: alias.to,
+            });
+        }
+
+        if let Some(mode) = self.strict {
+            strict::check(mode, &self.name, unused, self.on_unused_key)?;
+        }
+
+        Ok(parsed)
     }
 }
 
@@ -50,6 +115,7 @@ where
 mod tests {
     use super::*;
     use std::env;
+    use std::sync::atomic::{AtomicUsize, Ordering};
 
     use serde_yaml;
     use toml;
@@ -58,6 +124,7 @@ mod tests {
     use rst_common::standard::serde_json;
 
     use crate::format::{use_env, use_json, use_toml, use_yaml};
+    use crate::testing::EnvGuard;
     use crate::values::{StringValue, TupleValue};
 
     #[derive(Debug, Serialize, Deserialize)]
@@ -114,9 +181,167 @@ mod tests {
         assert_eq!("hello world".to_string(), out.unwrap().msg)
     }
 
+    #[test]
+    fn test_parse_strict_unused_keys() {
+        let input_value = StringValue::new("msg = \"hello world\"\nmax_con = 10".to_string());
+        let source = Source::new(input_value)
+            .with_name("test".to_string())
+            .with_strict(StrictMode::Deny);
+        let out: Result<Message, ConfigError> = source.parse(use_toml);
+        assert!(out.is_err());
+        assert_eq!(
+            out.unwrap_err(),
+            ConfigError::StrictError("`max_con` (source: test)".to_string())
+        )
+    }
+
+    #[test]
+    fn test_parse_strict_warn() {
+        static CALLED: AtomicUsize = AtomicUsize::new(0);
+
+        let input_value = StringValue::new("msg: hello world\nmax_con: 10".to_string());
+        let source = Source::new(input_value)
+            .with_name("test".to_string())
+            .with_strict(StrictMode::Warn)
+            .on_unused_key(|unused| {
+                assert_eq!(unused.source, "test".to_string());
+                assert_eq!(unused.key, "max_con".to_string());
+                CALLED.fetch_add(1, Ordering::SeqCst);
+            });
+        let out: Result<Message, ConfigError> = source.parse(use_yaml);
+        assert!(out.is_ok());
+        assert_eq!("hello world".to_string(), out.unwrap().msg);
+        assert_eq!(CALLED.load(Ordering::SeqCst), 1)
+    }
+
+    #[test]
+    fn test_parse_strict_env_unused_keys() {
+        let input = vec![
+            ("MSG".to_string(), "hello world".to_string()),
+            ("MAX_CON".to_string(), "10".to_string()),
+        ];
+
+        let source = Source::new(TupleValue::new(input))
+            .with_name("env:TEST_".to_string())
+            .with_strict(StrictMode::Deny);
+        let out: Result<Message, ConfigError> = source.parse(use_env);
+        assert!(out.is_err());
+        assert_eq!(
+            out.unwrap_err(),
+            ConfigError::StrictError("`MAX_CON` (source: env:TEST_)".to_string())
+        )
+    }
+
+    #[test]
+    fn test_parse_aliases() {
+        static CALLED: AtomicUsize = AtomicUsize::new(0);
+
+        let input_value = StringValue::new(r#"{"message": "hello world"}"#.to_string());
+        let source = Source::new(input_value)
+            .with_name("test".to_string())
+            .with_strict(StrictMode::Deny)
+            .with_aliases(vec![KeyAlias::deprecated(
+                "message".to_string(),
+                "msg".to_string(),
+            )])
+            .on_deprecation(|deprecation| {
+                assert_eq!(deprecation.source, "test".to_string());
+                assert_eq!(deprecation.key, "message".to_string());
+                assert_eq!(deprecation.replacement, "msg".to_string());
+                CALLED.fetch_add(1, Ordering::SeqCst);
+            });
+
+        let out: Result<Message, ConfigError> = source.parse(use_json);
+        assert!(out.is_ok());
+        assert_eq!("hello world".to_string(), out.unwrap().msg);
+        assert_eq!(CALLED.load(Ordering::SeqCst), 1)
+    }
+
+    #[test]
+    fn test_parse_aliases_conflicted() {
+        let input_value = StringValue::new("message: hello\nmsg: world".to_string());
+        let source = Source::new(input_value).with_aliases(vec![KeyAlias::new(
+            "message".to_string(),
+            "msg".to_string(),
+        )]);
+
+        let out: Result<Message, ConfigError> = source.parse(use_yaml);
+        assert!(out.is_err());
+        assert!(matches!(out.unwrap_err(), ConfigError::AliasError(_)))
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(crate = "self::serde")]
+    enum Backend {
+        Redis { url: String },
+    }
+
+    #[derive(Debug, Deserialize)]
+    #[serde(crate = "self::serde")]
+    struct Cache {
+        backend: Backend,
+        shards: std::collections::HashMap<u32, String>,
+    }
+
+    #[test]
+    fn test_parse_yaml_aliases_native_values() {
+        let input = "cache_backend: !Redis\n  url: redis://localhost\nshards:\n  1: a\n  2: b";
+        let source = Source::new(StringValue::new(input.to_string()))
+            .with_strict(StrictMode::Deny)
+            .with_aliases(vec![KeyAlias::new(
+                "cache_backend".to_string(),
+                "backend".to_string(),
+            )]);
+
+        let out: Result<Cache, ConfigError> = source.parse(use_yaml);
+        assert!(out.is_ok());
+
+        let cache = out.unwrap();
+        assert_eq!(
+            cache.backend,
+            Backend::Redis {
+                url: "redis://localhost".to_string()
+            }
+        );
+        assert_eq!(cache.shards.get(&2), Some(&"b".to_string()))
+    }
+
+    #[test]
+    fn test_parse_toml_aliases_native_values() {
+        let input = "[old]\nstarted_at = 2024-01-02T03:04:05Z\nname = \"app\"";
+        let source = Source::new(StringValue::new(input.to_string())).with_aliases(vec![
+            KeyAlias::new("old.name".to_string(), "name".to_string()),
+            KeyAlias::new("old.started_at".to_string(), "started_at".to_string()),
+        ]);
+
+        let out: Result<toml::Table, ConfigError> = source.parse(use_toml);
+        assert!(out.is_ok());
+
+        let table = out.unwrap();
+        assert!(table.get("old").is_none());
+        assert!(table
+            .get("started_at")
+            .is_some_and(toml::Value::is_datetime));
+        assert_eq!(table.get("name").and_then(toml::Value::as_str), Some("app"))
+    }
+
+    #[test]
+    fn test_parse_env_aliases() {
+        let input = vec![("MESSAGE".to_string(), "hello world".to_string())];
+        let source = Source::new(TupleValue::new(input)).with_aliases(vec![KeyAlias::new(
+            "message".to_string(),
+            "msg".to_string(),
+        )]);
+
+        let out: Result<Message, ConfigError> = source.parse(use_env);
+        assert!(out.is_ok());
+        assert_eq!("hello world".to_string(), out.unwrap().msg)
+    }
+
     #[test]
     fn test_parse_env() {
-        env::set_var("TEST_MSG", "hello world");
+        let mut guard = EnvGuard::new();
+        guard.set("TEST_MSG", "hello world");
 
         let vars: Vec<(String, String)> = env::vars().into_iter().collect();
         let input_source: Vec<(String, String)> = vars
@@ -0,0 +1,130 @@
+//! A `strict` module used to collect all configuration keys that not consumed by any fields
+//!
+//! The format parsers (`use_toml`, `use_yaml`, `use_json` and `use_env`) will report
+//! their unused keys through [`report`]. The report only collected when a [`crate::Source`]
+//! parsed with a [`StrictMode`], otherwise it will be ignored.
+use std::cell::RefCell;
+
+use crate::types::{ConfigError, StrictMode, UnusedKey};
+
+thread_local! {
+    static COLLECTOR: RefCell<Option<Vec<String>>> = const { RefCell::new(None) };
+}
+
+/// `collect` used to run given callback and collect all unused keys reported
+/// while the callback running
+pub(crate) fn collect<T>(cb: impl FnOnce() -> T) -> (T, Vec<String>) {
+    let previous = COLLECTOR.with(|collector| collector.replace(Some(Vec::new())));
+    let out = cb();
+    let keys = COLLECTOR.with(|collector| collector.replace(previous));
+
+    (out, keys.unwrap_or_default())
+}
+
+/// `report` used by the format parsers to report a key that not consumed by any fields
+pub(crate) fn report(key: String) {
+    COLLECTOR.with(|collector| {
+        if let Some(keys) = collector.borrow_mut().as_mut() {
+            keys.push(key);
+        }
+    })
+}
+
+/// `ignore_unused_key` is a default [`UnusedKey`] handler which will do nothing, a library
+/// should not write to the `stderr` by itself, so the caller must supply its own handler
+pub(crate) fn ignore_unused_key(_: &UnusedKey) {}
+
+/// `check` will decide what to do with all collected unused keys based on given [`StrictMode`]
+///
+/// - [`StrictMode::Warn`] will give each key to the `on_unused_key` handler
+/// - [`StrictMode::Deny`] will return [`ConfigError::StrictError`]
+pub(crate) fn check(
+    mode: StrictMode,
+    source: &str,
+    keys: Vec<String>,
+    on_unused_key: fn(&UnusedKey),
+) -> Result<(), ConfigError> {
+    if keys.is_empty() {
+        return Ok(());
+    }
+
+    match mode {
+        StrictMode::Warn => {
+            for key in keys {
+                on_unused_key(&UnusedKey {
+                    source: source.to_string(),
+                    key,
+                });
+            }
+
+            Ok(())
+        }
+        StrictMode::Deny => {
+            let unused: Vec<String> = keys
+                .iter()
+                .map(|key| format!("`{}` (source: {})", key, source))
+                .collect();
+
+            Err(ConfigError::StrictError(unused.join(", ")))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_collect_reported_keys() {
+        let (out, keys) = collect(|| {
+            report("key1".to_string());
+            report("key2".to_string());
+            "done"
+        });
+
+        assert_eq!(out, "done");
+        assert_eq!(keys, vec!["key1".to_string(), "key2".to_string()])
+    }
+
+    #[test]
+    fn test_report_without_collector() {
+        report("key1".to_string());
+
+        let (_, keys) = collect(|| ());
+        assert!(keys.is_empty())
+    }
+
+    #[test]
+    fn test_check_deny() {
+        let result = check(
+            StrictMode::Deny,
+            "test.toml",
+            vec!["max_con".to_string()],
+            ignore_unused_key,
+        );
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err(),
+            ConfigError::StrictError("`max_con` (source: test.toml)".to_string())
+        )
+    }
+
+    #[test]
+    fn test_check_warn() {
+        static CALLED: AtomicUsize = AtomicUsize::new(0);
+
+        let result = check(
+            StrictMode::Warn,
+            "test.toml",
+            vec!["max_con".to_string(), "db.hots".to_string()],
+            |unused| {
+                assert_eq!(unused.source, "test.toml".to_string());
+                CALLED.fetch_add(1, Ordering::SeqCst);
+            },
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(CALLED.load(Ordering::SeqCst), 2)
+    }
+}
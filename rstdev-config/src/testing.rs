@@ -0,0 +1,239 @@
+//! A `testing` module provides helpers to test the configuration sources without touching
+//! the global process state, enabled by `testing` feature
+//!
+//! - [`MemoryEnv`] is an in-memory [`EnvProvider`] that can be injected to the `Env` adapter
+//! - [`EnvGuard`] used to set the process environment variables and restore them when dropped
+//! - [`TempFile`] is a temporary configuration file fixture for the `File` adapter
+use std::cell::Cell;
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::types::EnvProvider;
+
+static ENV_LOCK: Mutex<()> = Mutex::new(());
+static FILE_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static GUARD_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// `MemoryEnv` is an [`EnvProvider`] that read the variables from an in-memory map
+///
+/// Example:
+///
+/// ```rust
+/// let provider = MemoryEnv::new().with_var("APP_PORT", "8080");
+/// let cfg: Config = Builder::new(from_env("APP_".to_string()).with_provider(provider))
+///     .fetch()?
+///     .parse(use_env)?;
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryEnv {
+    vars: BTreeMap<String, String>,
+}
+
+impl MemoryEnv {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_var(mut self, key: &str, value: &str) -> Self {
+        self.vars.insert(key.to_string(), value.to_string());
+        self
+    }
+}
+
+impl From<Vec<(String, String)>> for MemoryEnv {
+    fn from(vars: Vec<(String, String)>) -> Self {
+        Self {
+            vars: vars.into_iter().collect(),
+        }
+    }
+}
+
+impl EnvProvider for MemoryEnv {
+    fn vars(&self) -> Vec<(String, String)> {
+        self.vars
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+}
+
+/// `EnvGuard` used to change the process environment variables within a scope
+///
+/// All changed variables will be restored to their original values when the guard dropped.
+/// Only one guard can be alive at the same time, other tests that create a guard will wait
+/// until the current guard dropped, so they will not race with each other. A guard can be
+/// nested in the same thread, the nested guard must be dropped before its outer guard.
+pub struct EnvGuard {
+    saved: Vec<(String, Option<OsString>)>,
+    _lock: Option<MutexGuard<'static, ()>>,
+}
+
+impl EnvGuard {
+    pub fn new() -> Self {
+        let depth = GUARD_DEPTH.with(|depth| depth.replace(depth.get() + 1));
+        let lock = match depth {
+            0 => Some(
+                ENV_LOCK
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner()),
+            ),
+            _ => None,
+        };
+
+        Self {
+            saved: Vec::new(),
+            _lock: lock,
+        }
+    }
+
+    pub fn set(&mut self, key: &str, value: &str) {
+        self.save(key);
+        env::set_var(key, value);
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.save(key);
+        env::remove_var(key);
+    }
+
+    fn save(&mut self, key: &str) {
+        if !self.saved.iter().any(|(saved, _)| saved == key) {
+            self.saved.push((key.to_string(), env::var_os(key)));
+        }
+    }
+}
+
+impl Default for EnvGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for EnvGuard {
+    fn drop(&mut self) {
+        for (key, value) in self.saved.drain(..).rev() {
+            match value {
+                Some(value) => env::set_var(key, value),
+                None => env::remove_var(key),
+            }
+        }
+
+        GUARD_DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// `TempFile` is a temporary configuration file, the file will be removed when dropped
+///
+/// Example:
+///
+/// ```rust
+/// let file = TempFile::new("toml", "message = \"hello world\"")?;
+/// let cfg: Config = Builder::new(from_file(file.path())).fetch()?.parse(use_toml)?;
+/// ```
+pub struct TempFile {
+    path: PathBuf,
+}
+
+impl TempFile {
+    /// `new` used to write given content to a unique file in the temporary directory
+    /// with given file extension
+    pub fn new(ext: &str, content: &str) -> io::Result<Self> {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.subsec_nanos())
+            .unwrap_or_default();
+
+        let name = format!(
+            "rstdev-config-{}-{}-{}.{}",
+            std::process::id(),
+            FILE_COUNTER.fetch_add(1, Ordering::SeqCst),
+            nanos,
+            ext
+        );
+
+        let path = env::temp_dir().join(name);
+        fs::write(&path, content)?;
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// `filepath` used to get the file path as a string
+    pub fn filepath(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_env() {
+        let provider = MemoryEnv::new()
+            .with_var("APP_B", "2")
+            .with_var("APP_A", "1");
+
+        assert_eq!(
+            provider.vars(),
+            vec![
+                ("APP_A".to_string(), "1".to_string()),
+                ("APP_B".to_string(), "2".to_string())
+            ]
+        )
+    }
+
+    #[test]
+    fn test_env_guard_restore() {
+        let mut outer = EnvGuard::new();
+        outer.set("RSTDEV_GUARD_EXISTING", "original");
+
+        {
+            let mut guard = EnvGuard::new();
+            guard.set("RSTDEV_GUARD_EXISTING", "changed");
+            guard.set("RSTDEV_GUARD_EXISTING", "changed again");
+            guard.set("RSTDEV_GUARD_MISSING", "value");
+            assert_eq!(env::var("RSTDEV_GUARD_EXISTING").unwrap(), "changed again");
+            assert_eq!(env::var("RSTDEV_GUARD_MISSING").unwrap(), "value");
+        }
+
+        assert_eq!(env::var("RSTDEV_GUARD_EXISTING").unwrap(), "original");
+        assert!(env::var("RSTDEV_GUARD_MISSING").is_err());
+
+        drop(outer);
+        assert!(env::var("RSTDEV_GUARD_EXISTING").is_err())
+    }
+
+    #[test]
+    fn test_temp_file() {
+        let file = TempFile::new("toml", "message = \"hello world\"").unwrap();
+        let path = file.path().to_path_buf();
+
+        assert_eq!(path.extension().unwrap(), "toml");
+        assert_eq!(
+            fs::read_to_string(file.filepath()).unwrap(),
+            "message = \"hello world\""
+        );
+
+        drop(file);
+        assert!(!path.exists())
+    }
+}
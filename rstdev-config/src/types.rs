@@ -1,4 +1,5 @@
 //! A `types` is a module that provide base abstraction traits and also base types
+use std::fmt;
 
 use crate::source::Source;
 use rst_common::with_errors::thiserror::{self, Error};
@@ -6,10 +7,13 @@ use rst_common::with_errors::thiserror::{self, Error};
 /// ConfigError is a custom internal error that will be used
 /// when parsing of fetching data format
 ///
-/// There are two enum keys:
+/// There are five enum keys:
 ///
 /// - [`ConfigError::FormatError`]
 /// - [`ConfigError::ParseError`]
+/// - [`ConfigError::StrictError`]
+/// - [`ConfigError::AliasError`]
+/// - [`ConfigError::DiscoveryError`]
 #[derive(Error, Debug, PartialEq)]
 pub enum ConfigError {
     /// `FormatError` will be used when we're unable to read original format
@@ -19,6 +23,98 @@ pub enum ConfigError {
     /// `ParseError` will be used when we're unable to read configuration from some sources
     #[error("unable to parse configuration: {0}")]
     ParseError(String),
+
+    /// `StrictError` will be used when there are configuration keys that not consumed by any fields
+    /// while parsing in [`StrictMode::Deny`]
+    #[error("unused configuration keys: {0}")]
+    StrictError(String),
+
+    /// `AliasError` will be used when we're unable to resolve a [`KeyAlias`], like when
+    /// both of old and new keys are set with conflicting values
+    #[error("unable to resolve alias: {0}")]
+    AliasError(String),
+
+    /// `DiscoveryError` will be used when we're unable to find any configuration files
+    /// from all search paths
+    #[error("unable to discover configuration file: {0}")]
+    DiscoveryError(String),
+}
+
+/// StrictMode used to decide what to do when a configuration source contains keys
+/// that not consumed by any fields, like a typo of `max_con` instead of `max_conns`
+///
+/// - [`StrictMode::Warn`] will emit an [`UnusedKey`] warning for each unused key and continue
+/// - [`StrictMode::Deny`] will fail with [`ConfigError::StrictError`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StrictMode {
+    Warn,
+    Deny,
+}
+
+/// KeyAlias used to declare an old configuration key path that should be accepted
+/// as its new key path, example: `db.max_conns` to `db.pool.max_connections`
+///
+/// A deprecated alias will also emit a [`Deprecation`] warning each time its old key used
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyAlias {
+    pub from: String,
+    pub to: String,
+    pub deprecated: bool,
+}
+
+impl KeyAlias {
+    pub fn new(from: String, to: String) -> Self {
+        Self {
+            from,
+            to,
+            deprecated: false,
+        }
+    }
+
+    pub fn deprecated(from: String, to: String) -> Self {
+        Self {
+            from,
+            to,
+            deprecated: true,
+        }
+    }
+}
+
+/// Deprecation is a structured warning emitted when a configuration source still using
+/// a deprecated key path
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deprecation {
+    pub source: String,
+    pub key: String,
+    pub replacement: String,
+}
+
+impl fmt::Display for Deprecation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "configuration key `{}` is deprecated, use `{}` instead (source: {})",
+            self.key, self.replacement, self.source
+        )
+    }
+}
+
+/// UnusedKey is a structured warning emitted by [`StrictMode::Warn`] when a configuration source
+/// contains a key that not consumed by any fields
+#[derive(Debug, Clone, PartialEq)]
+pub struct UnusedKey {
+    pub source: String,
+    pub key: String,
+}
+
+impl fmt::Display for UnusedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unused configuration key `{}` (source: {})",
+            self.key, self.source
+        )
+    }
 }
 
 /// SourceFormatter is a public interface / trait that must be implemented
@@ -34,6 +130,16 @@ pub trait SourceFormatter<'a, TValue> {
     fn get_source_value(&'a self) -> TValue;
 }
 
+/// EnvProvider is a public interface / trait used by the `Env` adapter to read
+/// all available environment variables
+///
+/// By default, the `Env` adapter will read the variables from the current process,
+/// a custom provider can be injected to read the variables from other places, like an
+/// in-memory map while testing
+pub trait EnvProvider {
+    fn vars(&self) -> Vec<(String, String)>;
+}
+
 /// SourceParser is a public interface / trait that must be
 /// implemented by any adapters that need to parse config as
 /// a string from some source, like file, env vars or others
@@ -42,4 +148,10 @@ where
     TFormatter: for<'a> SourceFormatter<'a, TValue>,
 {
     fn fetch(&self) -> Result<Source<TFormatter, TValue>, ConfigError>;
+
+    /// `name` used to identify the source, like a file path, when reporting
+    /// unused configuration keys
+    fn name(&self) -> String {
+        String::from("unknown")
+    }
 }
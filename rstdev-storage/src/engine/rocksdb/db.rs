@@ -5,7 +5,10 @@ use std::sync::Arc;
 use rust_rocksdb::ColumnFamilyDescriptor;
 use rust_rocksdb::DB as CoreDB;
 
-use crate::types::Storage;
+use rst_common::standard::async_trait::async_trait;
+
+use crate::registry::Lifecycle;
+use crate::types::{Storage, StorageError};
 
 use super::options::Options;
 use super::types::RocksDBError;
@@ -77,3 +80,22 @@ impl Storage for DB {
         self
     }
 }
+
+/// Stopping the [`DB`] only releases its own instance, the database will be closed once all
+/// of its clones are dropped
+#[async_trait]
+impl Lifecycle for DB {
+    async fn start(&mut self) -> Result<(), StorageError> {
+        let db = self
+            .build()
+            .map_err(|err| StorageError::ConnectionError(err.to_string()))?;
+
+        self.set_db(db);
+        Ok(())
+    }
+
+    async fn stop(&mut self) -> Result<(), StorageError> {
+        self.set_db(None);
+        Ok(())
+    }
+}
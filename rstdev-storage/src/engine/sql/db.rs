@@ -0,0 +1,856 @@
+//! `db` is a module that provide a generic implementation of [`Storage`] and also
+//! [`SqlxConnectionBuilder`] shared by all `sql` engines
+//!
+//! Each engine only need to implement [`SqlEngine`], which tells the `sqlx` database type, its
+//! options and its own behaviors, then expose its storage as an alias of [`SqlDB`], example:
+//!
+//! ```rust
+//! pub struct MysqlEngine;
+//!
+//! impl SqlEngine for MysqlEngine {
+//!     type Database = MySql;
+//!     type Options = Options;
+//!     type Instance = Arc<SqlDB<Self>>;
+//!
+//!     const ISOLATION: IsolationPlacement = IsolationPlacement::BeforeBegin;
+//!     const BACKSLASH_ESCAPES: bool = true;
+//!
+//!     fn instance(db: SqlDB<Self>) -> Self::Instance {
+//!         Arc::new(db)
+//!     }
+//! }
+//!
+//! pub type MysqlDB = SqlDB<MysqlEngine>;
+//! ```
+#[cfg(feature = "tracing")]
+use core::future::Future;
+use std::sync::Arc;
+
+use rst_common::standard::async_trait::async_trait;
+use sqlx::pool::{Pool, PoolConnection};
+use sqlx::{Connection, Database, Executor, Transaction};
+
+use crate::engine::sql::error;
+#[cfg(feature = "tracing")]
+use crate::engine::sql::instrument::{self, QueryRows, TracingOptions};
+use crate::engine::sql::options::ConnectionMode;
+use crate::engine::sql::stats::{self, MetricsSink, PoolEvent, PoolMetrics, PoolStats};
+use crate::engine::sql::transaction::{
+    self, IsolationPlacement, TransactionFuture, TransactionOptions,
+};
+use crate::engine::sql::types::{
+    SqlxConnectionBuilder, SqlxOptionsBuilder, SqlxPoolOptionsBuilder,
+};
+use crate::registry::Lifecycle;
+use crate::retry::RetryPolicy;
+use crate::types::{Pinger, Storage, StorageError};
+
+/// `SqlConnection` is a `sqlx` connection type of given engine
+pub type SqlConnection<E> = <<E as SqlEngine>::Database as Database>::Connection;
+
+/// `SqlConnectOptions` is a `sqlx` connect options type of given engine
+pub type SqlConnectOptions<E> = <SqlConnection<E> as Connection>::Options;
+
+/// `SqlEngine` used to define all engine specific types and behaviors needed by [`SqlDB`]
+///
+/// - `Database`, a `sqlx` database type, like `MySql` or `Postgres`
+/// - `Options`, an engine options which build both of single and pooled connection options
+/// - `Instance`, a thread-safe instance type returned by [`Storage::get_instance`]
+/// - `ISOLATION`, when the transaction isolation level should be set
+/// - `BACKSLASH_ESCAPES`, if a backslash escapes a character inside a string literal, used to
+///   redact the traced statements
+pub trait SqlEngine: Sized + 'static {
+    type Database: Database;
+    type Options: SqlxOptionsBuilder<SqlxOptionType = SqlConnectOptions<Self>>
+        + SqlxPoolOptionsBuilder<SqlxDatabase = Self::Database>
+        + Send
+        + Sync;
+    type Instance: Send + Sync;
+
+    const ISOLATION: IsolationPlacement;
+    const BACKSLASH_ESCAPES: bool;
+
+    fn instance(db: SqlDB<Self>) -> Self::Instance;
+}
+
+/// `DB` will depends to the engine options to setup it's database connection and also it's instance
+pub struct DB<E>
+where
+    E: SqlEngine,
+{
+    opts: E::Options,
+    metrics: Arc<PoolMetrics>,
+    mode: ConnectionMode,
+    lazy: bool,
+}
+
+impl<E> DB<E>
+where
+    E: SqlEngine,
+{
+    pub fn new(opts: E::Options) -> Self {
+        Self {
+            opts,
+            metrics: Arc::new(PoolMetrics::default()),
+            mode: ConnectionMode::default(),
+            lazy: false,
+        }
+    }
+}
+
+#[async_trait]
+impl<E> SqlxConnectionBuilder for DB<E>
+where
+    E: SqlEngine,
+{
+    type SqlxDb = E::Database;
+    type SqlxDbConnection = SqlConnection<E>;
+
+    async fn build_single_conn(&self) -> Option<Result<Self::SqlxDbConnection, StorageError>> {
+        if !self.mode.has_single() {
+            return None;
+        }
+
+        let conn_opts = self.opts.common_options();
+        let hook = self.opts.session_hook();
+        let single_conn = async {
+            let mut conn = SqlConnection::<E>::connect_with(&conn_opts).await?;
+            if let Some(hook) = hook {
+                hook(&mut conn).await?;
+            }
+
+            Ok(conn)
+        }
+        .await
+        .map_err(error::classify_connection);
+
+        Some(single_conn)
+    }
+
+    async fn build_pool_conn(&self) -> Option<Result<Pool<Self::SqlxDb>, StorageError>> {
+        if !self.mode.has_pool() {
+            return None;
+        }
+
+        let conn_opts = self.opts.common_options();
+        let metrics = self.metrics.clone();
+        let hook = self.opts.session_hook();
+        let pool_conn_opts = self.opts.pool_options().after_connect(move |conn, _| {
+            metrics.record(PoolEvent::ConnectionOpened);
+            match &hook {
+                Some(hook) => hook(conn),
+                None => Box::pin(async { Ok(()) }),
+            }
+        });
+
+        // a lazy pool will not open any connection until it's used for the first time
+        if self.lazy {
+            return Some(Ok(pool_conn_opts.connect_lazy_with(conn_opts)));
+        }
+
+        let pool_conn = pool_conn_opts
+            .connect_with(conn_opts)
+            .await
+            .map_err(error::classify_connection);
+
+        Some(pool_conn)
+    }
+}
+
+/// `SqlDB` is an object that implement [`Storage`], this object will depends to [`DB`] to build
+/// it's connection types, a common or pooled connections
+pub struct SqlDB<E>
+where
+    E: SqlEngine,
+{
+    db: DB<E>,
+    retry: RetryPolicy,
+    #[cfg(feature = "tracing")]
+    tracing: TracingOptions,
+    pub single_conn: Option<SqlConnection<E>>,
+    pub pool_conn: Option<Pool<E::Database>>,
+}
+
+impl<E> SqlDB<E>
+where
+    E: SqlEngine,
+{
+    pub fn new(db: DB<E>) -> Self {
+        Self {
+            db,
+            retry: RetryPolicy::default(),
+            #[cfg(feature = "tracing")]
+            tracing: TracingOptions::default(),
+            single_conn: None,
+            pool_conn: None,
+        }
+    }
+
+    /// `with_retry` used to set the [`RetryPolicy`] used by [`Self::build_conns`], by default
+    /// it will only try to connect once
+    pub fn with_retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// `with_connection_mode` used to choose which connections will be built by [`Self::build_conns`],
+    /// by default it will build both of single and pooled connections
+    pub fn with_connection_mode(mut self, mode: ConnectionMode) -> Self {
+        self.db.mode = mode;
+        self
+    }
+
+    /// `with_lazy_pool` used to build the pool without opening any connection, the connections
+    /// will be opened on demand, so [`Self::build_conns`] will not fail when the database is not ready yet
+    pub fn with_lazy_pool(mut self) -> Self {
+        self.db.lazy = true;
+        self
+    }
+
+    /// `with_metrics_sink` used to forward all pool events to given [`MetricsSink`], it must be
+    /// set before [`Self::build_conns`]
+    pub fn with_metrics_sink(mut self, sink: Arc<dyn MetricsSink>) -> Self {
+        self.db.metrics = Arc::new(PoolMetrics::new(Some(sink)));
+        self
+    }
+
+    /// `with_tracing` used to set the [`TracingOptions`] used by [`Self::traced`]
+    #[cfg(feature = "tracing")]
+    pub fn with_tracing(mut self, tracing: TracingOptions) -> Self {
+        self.tracing = tracing;
+        self
+    }
+
+    /// `traced` used to run a query built from given statement inside a `sql.query` span, the same
+    /// statement will be given to the query builder, so the traced statement is always the executed one:
+    ///
+    /// ```rust
+    /// let result = postgres
+    ///     .traced("UPDATE users SET name = $1 WHERE id = $2", |sql| {
+    ///         sqlx::query(sql).bind(name).bind(id).execute(pool)
+    ///     })
+    ///     .await?;
+    /// ```
+    #[cfg(feature = "tracing")]
+    pub async fn traced<'q, T, F, Fut>(
+        &self,
+        statement: &'q str,
+        query: F,
+    ) -> Result<T, sqlx::Error>
+    where
+        T: QueryRows,
+        F: FnOnce(&'q str) -> Fut,
+        Fut: Future<Output = Result<T, sqlx::Error>>,
+    {
+        instrument::trace(
+            &self.tracing,
+            statement,
+            E::BACKSLASH_ESCAPES,
+            query(statement),
+        )
+        .await
+    }
+
+    /// `pool_stats` used to get a snapshot of the pool statistics, it will be `None` when
+    /// the pool connection has not been built
+    pub fn pool_stats(&self) -> Option<PoolStats> {
+        self.pool_conn
+            .as_ref()
+            .map(|pool| self.db.metrics.snapshot(pool))
+    }
+
+    /// `acquire` used to acquire a connection from the pool, its wait time will be recorded
+    /// in the pool statistics
+    pub async fn acquire(&self) -> Result<PoolConnection<E::Database>, StorageError> {
+        let pool = self
+            .pool_conn
+            .as_ref()
+            .ok_or(StorageError::ConnectionError(
+                "missing pool connection".to_string(),
+            ))?;
+
+        stats::acquire(pool, &self.db.metrics)
+            .await
+            .map_err(StorageError::from)
+    }
+
+    /// `build_conns` used to build the single and pooled connections, each connection
+    /// will be retried based on its [`RetryPolicy`]
+    pub async fn build_conns(&mut self) -> Result<(), StorageError> {
+        let db = &self.db;
+        let single_conn = self
+            .retry
+            .run(|| async { db.build_single_conn().await.transpose() })
+            .await?;
+
+        let pool_conn = self
+            .retry
+            .run(|| async { db.build_pool_conn().await.transpose() })
+            .await?;
+
+        if single_conn.is_some() {
+            self.single_conn = single_conn;
+        }
+
+        if pool_conn.is_some() {
+            self.pool_conn = pool_conn
+        }
+
+        Ok(())
+    }
+
+    /// `with_transaction` used to run given callback in a transaction using default [`TransactionOptions`]
+    pub async fn with_transaction<F, T>(&self, callback: F) -> Result<T, StorageError>
+    where
+        for<'c> &'c mut SqlConnection<E>: Executor<'c, Database = E::Database>,
+        F: for<'t> FnMut(&'t mut Transaction<'_, E::Database>) -> TransactionFuture<'t, T>,
+    {
+        self.with_transaction_opts(TransactionOptions::default(), callback)
+            .await
+    }
+
+    /// `with_transaction_opts` used to run given callback in a transaction using the pool connection
+    ///
+    /// The transaction will be committed when the callback succeed, or rolled back when it failed.
+    /// A transaction failed because of a deadlock or a serialization failure will be retried
+    ///
+    /// When the engine doesn't support isolation levels, the transaction will fail if it's set
+    pub async fn with_transaction_opts<F, T>(
+        &self,
+        opts: TransactionOptions,
+        callback: F,
+    ) -> Result<T, StorageError>
+    where
+        for<'c> &'c mut SqlConnection<E>: Executor<'c, Database = E::Database>,
+        F: for<'t> FnMut(&'t mut Transaction<'_, E::Database>) -> TransactionFuture<'t, T>,
+    {
+        let tx = transaction::run(
+            self.pool_conn.as_ref(),
+            &self.db.metrics,
+            opts,
+            E::ISOLATION,
+            callback,
+        );
+
+        #[cfg(feature = "tracing")]
+        let tx = instrument::trace_operation(&self.tracing, "transaction", tx);
+
+        tx.await
+    }
+}
+
+#[async_trait]
+impl<E> Storage for SqlDB<E>
+where
+    E: SqlEngine,
+{
+    type Instance = E::Instance;
+
+    fn get_instance(self) -> Self::Instance {
+        E::instance(self)
+    }
+
+    /// `ping` will ping all connections that have been built, including the pool connection
+    async fn ping(&mut self) -> Result<(), StorageError> {
+        let ping = ping_conns(
+            self.single_conn.as_mut(),
+            self.pool_conn.as_ref(),
+            &self.db.metrics,
+        );
+
+        #[cfg(feature = "tracing")]
+        let ping = instrument::trace_operation(&self.tracing, "ping", ping);
+
+        ping.await
+    }
+
+    /// `pinger` will ping a clone of the pool connection, the single connection cannot be shared
+    /// so there is no pinger when the pool connection has not been built
+    fn pinger(&self) -> Option<Box<dyn Pinger>> {
+        let pool = self.pool_conn.clone()?;
+        Some(Box::new(PoolPinger {
+            pool,
+            metrics: self.db.metrics.clone(),
+            #[cfg(feature = "tracing")]
+            tracing: self.tracing.clone(),
+        }))
+    }
+
+    /// `reconnect` will rebuild the single connection, and also the pool connection when it's
+    /// missing or closed. An open pool will not be rebuilt, since it will replace its broken
+    /// connections by itself
+    async fn reconnect(&mut self) -> Result<(), StorageError> {
+        if let Some(single_conn) = self.single_conn.take() {
+            let _ = single_conn.close_hard().await;
+        }
+
+        if let Some(single_conn) = self.db.build_single_conn().await {
+            self.single_conn = Some(single_conn?);
+        }
+
+        let pool_closed = match self.pool_conn.as_ref() {
+            Some(pool) => pool.is_closed(),
+            None => true,
+        };
+
+        if pool_closed {
+            if let Some(pool_conn) = self.db.build_pool_conn().await {
+                self.pool_conn = Some(pool_conn?);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn close(&mut self) -> Result<(), StorageError> {
+        if self.single_conn.is_some() {
+            self.single_conn
+                .take()
+                .map(|val| async move { val.close().await.map_err(error::classify_connection) })
+                .ok_or(StorageError::ConnectionError(
+                    "unable to close the connection".to_string(),
+                ))?
+                .await?;
+        }
+
+        if self.pool_conn.is_some() {
+            self.pool_conn
+                .take()
+                .map(|val| async move { val.close().await })
+                .ok_or(StorageError::ConnectionError(
+                    "unable to close the pool connection".to_string(),
+                ))?
+                .await;
+        }
+
+        Ok(())
+    }
+}
+
+/// `PoolPinger` is a [`Pinger`] of a cloned pool connection
+struct PoolPinger<TDB>
+where
+    TDB: Database,
+{
+    pool: Pool<TDB>,
+    metrics: Arc<PoolMetrics>,
+    #[cfg(feature = "tracing")]
+    tracing: TracingOptions,
+}
+
+#[async_trait]
+impl<TDB> Pinger for PoolPinger<TDB>
+where
+    TDB: Database,
+{
+    async fn ping(&self) -> Result<(), StorageError> {
+        let ping = ping_pool(&self.pool, &self.metrics);
+
+        #[cfg(feature = "tracing")]
+        let ping = instrument::trace_operation(&self.tracing, "ping", ping);
+
+        ping.await
+    }
+}
+
+async fn ping_conns<TDB>(
+    single_conn: Option<&mut TDB::Connection>,
+    pool_conn: Option<&Pool<TDB>>,
+    metrics: &PoolMetrics,
+) -> Result<(), StorageError>
+where
+    TDB: Database,
+{
+    if single_conn.is_none() && pool_conn.is_none() {
+        return Err(StorageError::PingError("unable to ping".to_string()));
+    }
+
+    if let Some(single_conn) = single_conn {
+        single_conn
+            .ping()
+            .await
+            .map_err(|err| StorageError::PingError(err.to_string()))?;
+    }
+
+    if let Some(pool) = pool_conn {
+        ping_pool(pool, metrics).await?;
+    }
+
+    Ok(())
+}
+
+async fn ping_pool<TDB>(pool: &Pool<TDB>, metrics: &PoolMetrics) -> Result<(), StorageError>
+where
+    TDB: Database,
+{
+    let mut conn = stats::acquire(pool, metrics)
+        .await
+        .map_err(|err| StorageError::PingError(err.to_string()))?;
+
+    conn.ping()
+        .await
+        .map_err(|err| StorageError::PingError(err.to_string()))
+}
+
+#[async_trait]
+impl<E> Lifecycle for SqlDB<E>
+where
+    E: SqlEngine,
+{
+    async fn start(&mut self) -> Result<(), StorageError> {
+        self.build_conns().await
+    }
+
+    async fn stop(&mut self) -> Result<(), StorageError> {
+        self.close().await
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use std::borrow::Cow;
+    use std::fmt;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use rst_common::with_tokio::tokio;
+    use sqlx::error::{DatabaseError, Error as StdError, ErrorKind};
+
+    use crate::engine::sql::options::DefaultDBPoolOptions;
+    use crate::engine::sql::sqlite::db::{SqliteDB, DB};
+    use crate::engine::sql::sqlite::options::{Options, SqliteDBOptions};
+
+    fn build_db() -> SqliteDB {
+        let opts = Options::new(
+            SqliteDBOptions::memory(),
+            DefaultDBPoolOptions {
+                max_conns: Some(2),
+                min_conns: None,
+                idle_duration: None,
+                lifetime_duration: None,
+                acquire_timeout: None,
+            },
+        );
+
+        SqliteDB::new(DB::new(opts.unwrap()))
+    }
+
+    #[derive(Debug)]
+    struct SerializationError;
+
+    impl fmt::Display for SerializationError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "could not serialize access")
+        }
+    }
+
+    impl std::error::Error for SerializationError {}
+
+    impl DatabaseError for SerializationError {
+        fn message(&self) -> &str {
+            "could not serialize access"
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            Some(Cow::Borrowed("40001"))
+        }
+
+        fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Other
+        }
+    }
+
+    async fn build_settings_db() -> SqliteDB {
+        let mut sqlite = build_db();
+        assert!(sqlite.build_conns().await.is_ok());
+
+        let created = sqlx::query("CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT)")
+            .execute(sqlite.pool_conn.as_ref().unwrap())
+            .await;
+        assert!(created.is_ok());
+
+        sqlite
+    }
+
+    async fn count_settings(sqlite: &SqliteDB) -> i64 {
+        let count: (i64,) = sqlx::query_as("SELECT COUNT(*) FROM settings")
+            .fetch_one(sqlite.pool_conn.as_ref().unwrap())
+            .await
+            .unwrap();
+
+        count.0
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_with_transaction_commit() {
+        let sqlite = build_settings_db().await;
+        let result = sqlite
+            .with_transaction(|tx| {
+                Box::pin(async move {
+                    sqlx::query("INSERT INTO settings (key, value) VALUES ('a', 'b')")
+                        .execute(&mut **tx)
+                        .await?;
+
+                    Ok(1)
+                })
+            })
+            .await;
+
+        assert_eq!(result, Ok(1));
+        assert_eq!(count_settings(&sqlite).await, 1)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_with_transaction_rollback() {
+        let sqlite = build_settings_db().await;
+        let result: Result<(), StorageError> = sqlite
+            .with_transaction(|tx| {
+                Box::pin(async move {
+                    sqlx::query("INSERT INTO settings (key, value) VALUES ('a', 'b')")
+                        .execute(&mut **tx)
+                        .await?;
+
+                    Err(StdError::RowNotFound)
+                })
+            })
+            .await;
+
+        assert_eq!(result, Err(StorageError::NotFoundError));
+        assert_eq!(count_settings(&sqlite).await, 0)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_with_transaction_retry() {
+        let sqlite = build_settings_db().await;
+        let attempts = AtomicU32::new(0);
+
+        let opts = TransactionOptions {
+            initial_backoff: core::time::Duration::from_millis(1),
+            ..TransactionOptions::default()
+        };
+
+        let result = sqlite
+            .with_transaction_opts(opts, |tx| {
+                let attempt = attempts.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move {
+                    sqlx::query("INSERT INTO settings (key, value) VALUES ('a', 'b')")
+                        .execute(&mut **tx)
+                        .await?;
+
+                    if attempt < 2 {
+                        return Err(StdError::Database(Box::new(SerializationError)));
+                    }
+
+                    Ok(())
+                })
+            })
+            .await;
+
+        assert!(result.is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+        assert_eq!(count_settings(&sqlite).await, 1)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_with_transaction_retry_exhausted() {
+        let sqlite = build_settings_db().await;
+        let attempts = AtomicU32::new(0);
+
+        let opts = TransactionOptions {
+            max_retries: 1,
+            initial_backoff: core::time::Duration::from_millis(1),
+            ..TransactionOptions::default()
+        };
+
+        let result: Result<(), StorageError> = sqlite
+            .with_transaction_opts(opts, |_| {
+                attempts.fetch_add(1, Ordering::SeqCst);
+                Box::pin(async move { Err(StdError::Database(Box::new(SerializationError))) })
+            })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), 2)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_reconnect() {
+        let mut sqlite = build_db();
+        assert!(sqlite.build_conns().await.is_ok());
+
+        sqlite.single_conn = None;
+        assert!(sqlite.ping().await.is_ok());
+
+        sqlite.pool_conn = None;
+        assert!(sqlite.ping().await.is_err());
+
+        assert!(sqlite.reconnect().await.is_ok());
+        assert!(sqlite.ping().await.is_ok())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_reconnect_pool_only() {
+        let mut sqlite = build_db().with_connection_mode(ConnectionMode::PoolOnly);
+        assert!(sqlite.build_conns().await.is_ok());
+
+        sqlite.pool_conn.as_ref().unwrap().close().await;
+        assert!(sqlite.ping().await.is_err());
+
+        assert!(sqlite.reconnect().await.is_ok());
+        assert!(sqlite.single_conn.is_none());
+        assert!(!sqlite.pool_conn.as_ref().unwrap().is_closed());
+        assert!(sqlite.ping().await.is_ok());
+
+        sqlite.pool_conn = None;
+        assert!(sqlite.reconnect().await.is_ok());
+        assert!(sqlite.ping().await.is_ok())
+    }
+
+    #[cfg(feature = "tracing")]
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_traced() {
+        let mut sqlite = build_db();
+        assert!(sqlite.build_conns().await.is_ok());
+
+        let pool = sqlite.pool_conn.clone().unwrap();
+        let result = sqlite
+            .traced("SELECT 'secret' UNION SELECT $1", |sql| {
+                sqlx::query(sql).bind("value").fetch_all(&pool)
+            })
+            .await;
+
+        assert_eq!(result.unwrap().len(), 2);
+        assert!(sqlite.ping().await.is_ok())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_pool_stats() {
+        let opts = Options::new(
+            SqliteDBOptions::memory(),
+            DefaultDBPoolOptions {
+                max_conns: Some(1),
+                min_conns: None,
+                idle_duration: None,
+                lifetime_duration: None,
+                acquire_timeout: Some(core::time::Duration::from_millis(50)),
+            },
+        );
+
+        let mut sqlite = SqliteDB::new(DB::new(opts.unwrap()));
+        assert!(sqlite.pool_stats().is_none());
+        assert!(sqlite.build_conns().await.is_ok());
+
+        let conn = sqlite.acquire().await;
+        assert!(conn.is_ok());
+        assert!(sqlite.acquire().await.is_err());
+
+        let stats = sqlite.pool_stats().unwrap();
+        assert_eq!(stats.size, 1);
+        assert_eq!(stats.in_use, 1);
+        assert_eq!(stats.max_conns, 1);
+        assert_eq!(stats.opened, 1);
+        assert_eq!(stats.closed, 0);
+        assert_eq!(stats.acquire_wait.count, 1);
+        assert_eq!(stats.acquire_timeouts, 1);
+
+        drop(conn);
+        sqlite.pool_conn.as_ref().unwrap().close().await;
+        let stats = sqlite.pool_stats().unwrap();
+        assert_eq!(stats.size, 0);
+        assert_eq!(stats.opened, 1);
+        assert_eq!(stats.closed, 1);
+        assert!(sqlite.close().await.is_ok())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_build_conns_mode() {
+        let mut sqlite = build_db().with_connection_mode(ConnectionMode::PoolOnly);
+        assert!(sqlite.build_conns().await.is_ok());
+        assert!(sqlite.single_conn.is_none());
+        assert!(sqlite.ping().await.is_ok());
+
+        let pinger = sqlite.pinger().unwrap();
+        assert!(pinger.ping().await.is_ok());
+        assert!(sqlite.close().await.is_ok());
+        assert!(pinger.ping().await.is_err());
+
+        let mut sqlite = build_db().with_connection_mode(ConnectionMode::SingleOnly);
+        assert!(sqlite.build_conns().await.is_ok());
+        assert!(sqlite.pool_conn.is_none());
+        assert!(sqlite.pinger().is_none());
+        assert!(sqlite.ping().await.is_ok())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_build_conns_lazy_pool() {
+        let mut file = SqliteDBOptions::file(std::env::temp_dir().join("rstdev-missing/app.db"));
+        file.create_if_missing = false;
+
+        let opts = Options::new(
+            file,
+            DefaultDBPoolOptions {
+                max_conns: Some(1),
+                min_conns: None,
+                idle_duration: None,
+                lifetime_duration: None,
+                acquire_timeout: Some(core::time::Duration::from_millis(100)),
+            },
+        );
+
+        let mut sqlite = SqliteDB::new(DB::new(opts.unwrap()))
+            .with_connection_mode(ConnectionMode::PoolOnly)
+            .with_lazy_pool();
+
+        assert!(sqlite.build_conns().await.is_ok());
+        assert_eq!(sqlite.pool_stats().unwrap().size, 0);
+        assert!(sqlite.ping().await.is_err());
+
+        let mut sqlite = build_db()
+            .with_connection_mode(ConnectionMode::PoolOnly)
+            .with_lazy_pool();
+
+        assert!(sqlite.build_conns().await.is_ok());
+        assert!(sqlite.ping().await.is_ok());
+        assert_eq!(sqlite.pool_stats().unwrap().opened, 1)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_classified_errors() {
+        let sqlite = build_settings_db().await;
+        let pool = sqlite.pool_conn.as_ref().unwrap();
+
+        let sql = "INSERT INTO settings (key, value) VALUES ('a', 'b')";
+        assert!(sqlx::query(sql).execute(pool).await.is_ok());
+
+        let duplicate = sqlx::query(sql)
+            .execute(pool)
+            .await
+            .map_err(StorageError::from);
+        assert!(matches!(
+            duplicate,
+            Err(StorageError::UniqueViolationError { .. })
+        ));
+
+        let result: Result<(), StorageError> = sqlite
+            .with_transaction(|_| {
+                Box::pin(async move { Err(StdError::Database(Box::new(SerializationError))) })
+            })
+            .await;
+
+        let err = result.unwrap_err();
+        assert!(matches!(err, StorageError::SerializationError(_)));
+        assert!(err.is_retryable())
+    }
+}
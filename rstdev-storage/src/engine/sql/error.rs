@@ -0,0 +1,253 @@
+//! `error` provides a mapping from `sqlx` errors to the classified [`StorageError`] variants
+//!
+//! A database error will be classified by its kind, its SQLSTATE code or its Mysql error number,
+//! so Mysql and Postgres will return the same variant for the same failure. Any error that
+//! cannot be classified will be mapped to [`StorageError::QueryError`]
+use sqlx::error::{DatabaseError, ErrorKind};
+
+use crate::types::StorageError;
+
+const SERIALIZATION_FAILURE: &str = "40001";
+const DEADLOCK_DETECTED: &str = "40P01";
+const LOCK_NOT_AVAILABLE: &str = "55P03";
+const QUERY_CANCELED: &str = "57014";
+const ADMIN_SHUTDOWN: &str = "57P01";
+const CONNECTION_EXCEPTION_CLASS: &str = "08";
+const TOO_MANY_CONNECTIONS: &str = "53300";
+const CANNOT_CONNECT_NOW: &str = "57P03";
+
+#[cfg(feature = "mysql")]
+mod mysql_codes {
+    pub const ER_LOCK_WAIT_TIMEOUT: u16 = 1205;
+    pub const ER_LOCK_DEADLOCK: u16 = 1213;
+    pub const CR_SERVER_GONE_ERROR: u16 = 2006;
+    pub const CR_SERVER_LOST: u16 = 2013;
+    pub const ER_QUERY_TIMEOUT: u16 = 3024;
+    pub const ER_CON_COUNT_ERROR: u16 = 1040;
+}
+
+impl From<sqlx::Error> for StorageError {
+    fn from(err: sqlx::Error) -> Self {
+        classify(&err)
+    }
+}
+
+/// `classify` used to map given `sqlx` error to its [`StorageError`] variant without consuming it
+pub fn classify(err: &sqlx::Error) -> StorageError {
+    match err {
+        sqlx::Error::RowNotFound => StorageError::NotFoundError,
+        sqlx::Error::PoolTimedOut => StorageError::PoolExhaustedError(err.to_string()),
+        sqlx::Error::PoolClosed | sqlx::Error::WorkerCrashed | sqlx::Error::Io(_) => {
+            StorageError::ConnectionLostError(err.to_string())
+        }
+        sqlx::Error::Configuration(_) => StorageError::BuildOptionsError(err.to_string()),
+        sqlx::Error::Database(db_err) => classify_database(db_err.as_ref()),
+        _ => StorageError::QueryError(err.to_string()),
+    }
+}
+
+/// `classify_connection` used to classify an error while opening or closing a connection, an error
+/// that cannot be classified will be mapped to [`StorageError::ConnectionError`] instead
+///
+/// An IO error, like a refused connection or a failed DNS lookup, will also be mapped to
+/// [`StorageError::ConnectionError`], since there is no established connection to be lost.
+/// A database that is starting up or has too many connections is mapped the same way, while any
+/// other database error, like a failed authentication or an unknown database, and a TLS error
+/// will be mapped to [`StorageError::ConnectionRejectedError`]
+pub fn classify_connection(err: sqlx::Error) -> StorageError {
+    match &err {
+        sqlx::Error::Io(_) => return StorageError::ConnectionError(err.to_string()),
+        sqlx::Error::Tls(_) => return StorageError::ConnectionRejectedError(err.to_string()),
+        sqlx::Error::Database(db_err) if is_unavailable(db_err.as_ref()) => {
+            return StorageError::ConnectionError(err.to_string())
+        }
+        _ => {}
+    }
+
+    match classify(&err) {
+        StorageError::QueryError(_) if matches!(err, sqlx::Error::Database(_)) => {
+            StorageError::ConnectionRejectedError(err.to_string())
+        }
+        StorageError::QueryError(_) => StorageError::ConnectionError(err.to_string()),
+        classified => classified,
+    }
+}
+
+/// `is_unavailable` used to check if the database refused a new connection only for now
+fn is_unavailable(db_err: &dyn DatabaseError) -> bool {
+    #[cfg(feature = "mysql")]
+    if let Some(mysql_err) = db_err.try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>() {
+        return mysql_err.number() == mysql_codes::ER_CON_COUNT_ERROR;
+    }
+
+    matches!(
+        db_err.code().as_deref(),
+        Some(TOO_MANY_CONNECTIONS) | Some(CANNOT_CONNECT_NOW)
+    )
+}
+
+fn classify_database(db_err: &dyn DatabaseError) -> StorageError {
+    let message = db_err.message().to_string();
+    match db_err.kind() {
+        ErrorKind::UniqueViolation => {
+            return StorageError::UniqueViolationError {
+                constraint: constraint(db_err),
+                message,
+            }
+        }
+        ErrorKind::ForeignKeyViolation => {
+            return StorageError::ForeignKeyViolationError {
+                constraint: constraint(db_err),
+                message,
+            }
+        }
+        ErrorKind::CheckViolation => {
+            return StorageError::CheckViolationError {
+                constraint: constraint(db_err),
+                message,
+            }
+        }
+        _ => {}
+    }
+
+    #[cfg(feature = "mysql")]
+    if let Some(mysql_err) = db_err.try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>() {
+        match mysql_err.number() {
+            mysql_codes::ER_LOCK_DEADLOCK => return StorageError::DeadlockError(message),
+            mysql_codes::ER_LOCK_WAIT_TIMEOUT | mysql_codes::ER_QUERY_TIMEOUT => {
+                return StorageError::TimeoutError(message)
+            }
+            mysql_codes::CR_SERVER_GONE_ERROR | mysql_codes::CR_SERVER_LOST => {
+                return StorageError::ConnectionLostError(message)
+            }
+            _ => {}
+        }
+    }
+
+    match db_err.code().as_deref() {
+        Some(SERIALIZATION_FAILURE) => StorageError::SerializationError(message),
+        Some(DEADLOCK_DETECTED) => StorageError::DeadlockError(message),
+        Some(LOCK_NOT_AVAILABLE) | Some(QUERY_CANCELED) => StorageError::TimeoutError(message),
+        Some(ADMIN_SHUTDOWN) => StorageError::ConnectionLostError(message),
+        Some(code) if code.starts_with(CONNECTION_EXCEPTION_CLASS) => {
+            StorageError::ConnectionLostError(message)
+        }
+        _ => StorageError::QueryError(db_err.to_string()),
+    }
+}
+
+/// `constraint` used to get the violated constraint name, Postgres sends it as an error field
+/// while Mysql only mentions it in the error message
+fn constraint(db_err: &dyn DatabaseError) -> Option<String> {
+    if let Some(constraint) = db_err.constraint() {
+        return Some(constraint.to_string());
+    }
+
+    #[cfg(feature = "mysql")]
+    if db_err
+        .try_downcast_ref::<sqlx::mysql::MySqlDatabaseError>()
+        .is_some()
+    {
+        return mysql_constraint(db_err.message());
+    }
+
+    None
+}
+
+/// `mysql_constraint` used to parse the constraint name from these Mysql messages:
+///
+/// - `Duplicate entry 'a' for key 'users.email'`
+/// - `Cannot add or update a child row: a foreign key constraint fails (... CONSTRAINT `fk_user` ...)`
+/// - `Check constraint 'age_positive' is violated.`
+#[cfg(feature = "mysql")]
+fn mysql_constraint(message: &str) -> Option<String> {
+    let patterns = [
+        ("for key '", '\''),
+        ("CONSTRAINT `", '`'),
+        ("constraint '", '\''),
+    ];
+    patterns.iter().find_map(|(prefix, end)| {
+        let start = message.find(prefix)? + prefix.len();
+        let name = message[start..].split(*end).next()?;
+        match name.is_empty() {
+            true => None,
+            false => Some(name.to_string()),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_non_database_error() {
+        assert_eq!(
+            StorageError::from(sqlx::Error::RowNotFound),
+            StorageError::NotFoundError
+        );
+        assert!(matches!(
+            classify(&sqlx::Error::PoolTimedOut),
+            StorageError::PoolExhaustedError(_)
+        ));
+        assert!(matches!(
+            classify(&sqlx::Error::PoolClosed),
+            StorageError::ConnectionLostError(_)
+        ));
+        assert!(classify(&sqlx::Error::PoolTimedOut).is_retryable());
+        assert!(!classify(&sqlx::Error::RowNotFound).is_retryable());
+        assert!(matches!(
+            classify(&sqlx::Error::ColumnNotFound("id".to_string())),
+            StorageError::QueryError(_)
+        ))
+    }
+
+    #[test]
+    fn test_classify_connection() {
+        assert!(matches!(
+            classify_connection(sqlx::Error::PoolClosed),
+            StorageError::ConnectionLostError(_)
+        ));
+        assert!(matches!(
+            classify_connection(sqlx::Error::Configuration("invalid port".into())),
+            StorageError::BuildOptionsError(_)
+        ));
+        assert!(matches!(
+            classify_connection(sqlx::Error::Protocol("unexpected packet".to_string())),
+            StorageError::ConnectionError(_)
+        ));
+
+        let refused =
+            || sqlx::Error::Io(std::io::Error::from(std::io::ErrorKind::ConnectionRefused));
+        assert!(matches!(
+            classify_connection(refused()),
+            StorageError::ConnectionError(_)
+        ));
+        assert!(matches!(
+            classify(&refused()),
+            StorageError::ConnectionLostError(_)
+        ));
+        assert!(matches!(
+            classify_connection(sqlx::Error::Tls("invalid certificate".into())),
+            StorageError::ConnectionRejectedError(_)
+        ))
+    }
+
+    #[cfg(feature = "mysql")]
+    #[test]
+    fn test_mysql_constraint() {
+        assert_eq!(
+            mysql_constraint("Duplicate entry 'a@b.c' for key 'users.email'"),
+            Some("users.email".to_string())
+        );
+        assert_eq!(
+            mysql_constraint("Cannot add or update a child row: a foreign key constraint fails (`app`.`posts`, CONSTRAINT `fk_user` FOREIGN KEY (`user_id`) REFERENCES `users` (`id`))"),
+            Some("fk_user".to_string())
+        );
+        assert_eq!(
+            mysql_constraint("Check constraint 'age_positive' is violated."),
+            Some("age_positive".to_string())
+        );
+        assert_eq!(mysql_constraint("Lock wait timeout exceeded"), None)
+    }
+}
@@ -0,0 +1,421 @@
+//! `instrument` provides query tracing hooks for all `sql` storages, only available with the
+//! `tracing` feature
+//!
+//! Each traced query will be wrapped in a `sql.query` span which contains the redacted statement,
+//! the duration, the number of rows and the error if any. The built-in operations, like a ping,
+//! a transaction or a migration, will be wrapped in a `sql.operation` span. A query or an operation
+//! slower than the slow query threshold will also be logged at the `warn` level using
+//! `rstdev_storage::slow_query` target.
+use core::fmt;
+use core::future::Future;
+use core::time::Duration;
+use std::time::Instant;
+
+use tracing::field::Empty;
+use tracing::{Instrument, Span};
+
+/// `SLOW_QUERY_TARGET` is a `tracing` target used by the slow query logs
+pub const SLOW_QUERY_TARGET: &str = "rstdev_storage::slow_query";
+
+/// `TracingOptions` used to configure the query tracing
+///
+/// - `slow_query_threshold`, a minimum duration of a query to be logged as a slow query, the slow
+///   query log will be disabled when it's missing
+#[derive(Debug, Clone)]
+pub struct TracingOptions {
+    pub slow_query_threshold: Option<Duration>,
+}
+
+impl Default for TracingOptions {
+    fn default() -> Self {
+        Self {
+            slow_query_threshold: Some(Duration::from_secs(1)),
+        }
+    }
+}
+
+/// `QueryRows` used to get a number of rows from a query output, it's the affected rows
+/// for an `execute` query or the fetched rows for a `fetch` query
+pub trait QueryRows {
+    fn rows(&self) -> Option<u64>;
+}
+
+impl<T> QueryRows for Vec<T> {
+    fn rows(&self) -> Option<u64> {
+        Some(self.len() as u64)
+    }
+}
+
+impl<T> QueryRows for Option<T> {
+    fn rows(&self) -> Option<u64> {
+        Some(self.is_some() as u64)
+    }
+}
+
+impl QueryRows for () {
+    fn rows(&self) -> Option<u64> {
+        None
+    }
+}
+
+#[cfg(feature = "mysql")]
+impl QueryRows for sqlx::mysql::MySqlQueryResult {
+    fn rows(&self) -> Option<u64> {
+        Some(self.rows_affected())
+    }
+}
+
+#[cfg(feature = "postgresql")]
+impl QueryRows for sqlx::postgres::PgQueryResult {
+    fn rows(&self) -> Option<u64> {
+        Some(self.rows_affected())
+    }
+}
+
+#[cfg(feature = "sqlite")]
+impl QueryRows for sqlx::sqlite::SqliteQueryResult {
+    fn rows(&self) -> Option<u64> {
+        Some(self.rows_affected())
+    }
+}
+
+/// `redact` used to replace all string and numeric literals in given statement with `?`,
+/// so the statement can be traced without leaking any values
+///
+/// Quoted identifiers and bind placeholders like `$1` will be kept as they are. Beside of the standard
+/// string literals, it also supports `E'...'` escape strings and `$$...$$` or `$tag$...$tag$` dollar
+/// quoted strings. The `backslash_escapes` used to tell if a backslash escapes the next character in a
+/// standard string literal, like Mysql `'it\'s'`, an `E'...'` string will always support it
+pub fn redact(statement: &str, backslash_escapes: bool) -> String {
+    let mut redacted = String::with_capacity(statement.len());
+    let mut rest = statement;
+    let mut prev: Option<char> = None;
+
+    while let Some(c) = rest.chars().next() {
+        let literal = match c {
+            '\'' => Some(string_len(rest, backslash_escapes)),
+            'E' | 'e' if !is_word(prev) && rest[1..].starts_with('\'') => {
+                Some(1 + string_len(&rest[1..], true))
+            }
+            '$' if !is_word(prev) => dollar_string_len(rest),
+            c if c.is_ascii_digit() && !is_word(prev) => Some(
+                rest.find(|next: char| !next.is_ascii_alphanumeric() && next != '.')
+                    .unwrap_or(rest.len()),
+            ),
+            _ => None,
+        };
+
+        let len = match literal {
+            Some(len) => {
+                redacted.push('?');
+                prev = Some('?');
+                len
+            }
+            None => {
+                // keep the whole quoted identifier, so its content will not be redacted
+                let len = match c {
+                    '"' | '`' => rest[1..].find(c).map(|end| end + 2).unwrap_or(rest.len()),
+                    c => c.len_utf8(),
+                };
+
+                redacted.push_str(&rest[..len]);
+                prev = Some(c);
+                len
+            }
+        };
+
+        rest = &rest[len..];
+    }
+
+    redacted
+}
+
+fn is_word(c: Option<char>) -> bool {
+    matches!(c, Some(c) if c.is_alphanumeric() || c == '_' || c == '$')
+}
+
+/// `string_len` used to get the length of a string literal at the start of given input, including for
+/// its escaped quotes. An unterminated string will take the rest of the input
+fn string_len(input: &str, backslash_escapes: bool) -> usize {
+    let mut chars = input.char_indices().skip(1).peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '\\' if backslash_escapes => {
+                chars.next();
+            }
+            '\'' if chars.peek().is_some_and(|(_, next)| *next == '\'') => {
+                chars.next();
+            }
+            '\'' => return index + 1,
+            _ => {}
+        }
+    }
+
+    input.len()
+}
+
+/// `dollar_string_len` used to get the length of a dollar quoted string at the start of given input,
+/// it will be `None` when the input is not started with a dollar quote tag, like a `$1` placeholder
+fn dollar_string_len(input: &str) -> Option<usize> {
+    let tag_len = input[1..].find('$')? + 2;
+    let tag = &input[..tag_len];
+    let name = &tag[1..tag_len - 1];
+
+    let valid = name.chars().all(|c| c.is_alphanumeric() || c == '_')
+        && !name.starts_with(|c: char| c.is_ascii_digit());
+
+    if !valid {
+        return None;
+    }
+
+    let len = input[tag_len..]
+        .find(tag)
+        .map(|end| tag_len + end + tag_len)
+        .unwrap_or(input.len());
+
+    Some(len)
+}
+
+/// `trace` used to run given query future inside a `sql.query` span
+pub async fn trace<T, Fut>(
+    opts: &TracingOptions,
+    statement: &str,
+    backslash_escapes: bool,
+    query: Fut,
+) -> Result<T, sqlx::Error>
+where
+    T: QueryRows,
+    Fut: Future<Output = Result<T, sqlx::Error>>,
+{
+    let redacted = redact(statement, backslash_escapes);
+    let span = tracing::info_span!(
+        "sql.query",
+        db.statement = %redacted,
+        duration_ms = Empty,
+        rows = Empty,
+        error = Empty,
+    );
+
+    let started = Instant::now();
+    let result = query.instrument(span.clone()).await;
+    if let Ok(out) = &result {
+        if let Some(rows) = out.rows() {
+            span.record("rows", rows);
+        }
+    }
+
+    record(
+        opts,
+        &span,
+        &redacted,
+        started.elapsed(),
+        result.as_ref().err().map(|err| err as &dyn fmt::Display),
+    );
+    result
+}
+
+/// `trace_operation` used to run a built-in operation, like a ping, a transaction or a migration,
+/// inside a `sql.operation` span
+pub async fn trace_operation<T, E, Fut>(
+    opts: &TracingOptions,
+    operation: &str,
+    future: Fut,
+) -> Result<T, E>
+where
+    E: fmt::Display,
+    Fut: Future<Output = Result<T, E>>,
+{
+    let span = tracing::info_span!(
+        "sql.operation",
+        db.operation = operation,
+        duration_ms = Empty,
+        error = Empty,
+    );
+
+    let started = Instant::now();
+    let result = future.instrument(span.clone()).await;
+
+    record(
+        opts,
+        &span,
+        operation,
+        started.elapsed(),
+        result.as_ref().err().map(|err| err as &dyn fmt::Display),
+    );
+    result
+}
+
+/// `record` used to record the duration and the error of a finished span, and log it as a slow query
+/// when it's slower than the threshold
+fn record(
+    opts: &TracingOptions,
+    span: &Span,
+    statement: &str,
+    elapsed: Duration,
+    error: Option<&dyn fmt::Display>,
+) {
+    span.record("duration_ms", elapsed.as_millis() as u64);
+    if let Some(err) = error {
+        span.record("error", tracing::field::display(err));
+    }
+
+    if let Some(threshold) = opts.slow_query_threshold {
+        if elapsed >= threshold {
+            tracing::warn!(
+                target: SLOW_QUERY_TARGET,
+                parent: span,
+                statement = %statement,
+                duration_ms = elapsed.as_millis() as u64,
+                "slow query"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use rst_common::with_tokio::tokio;
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Event, Level, Metadata, Subscriber};
+
+    #[derive(Default)]
+    struct Counter {
+        spans: AtomicU64,
+        slow_queries: AtomicUsize,
+    }
+
+    struct CountingSubscriber(Arc<Counter>);
+
+    impl Subscriber for CountingSubscriber {
+        fn enabled(&self, _: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, _: &Attributes<'_>) -> Id {
+            Id::from_u64(self.0.spans.fetch_add(1, Ordering::SeqCst) + 1)
+        }
+
+        fn record(&self, _: &Id, _: &Record<'_>) {}
+
+        fn record_follows_from(&self, _: &Id, _: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let metadata = event.metadata();
+            if *metadata.level() == Level::WARN && metadata.target() == SLOW_QUERY_TARGET {
+                self.0.slow_queries.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        fn enter(&self, _: &Id) {}
+
+        fn exit(&self, _: &Id) {}
+    }
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact(
+                "SELECT * FROM users WHERE name = 'it''s me' AND age > 21 AND id = $1",
+                false
+            ),
+            "SELECT * FROM users WHERE name = ? AND age > ? AND id = $1"
+        );
+        assert_eq!(
+            redact(
+                "INSERT INTO `logs2` (\"col 1\", v1) VALUES (1.5, 'x')",
+                false
+            ),
+            "INSERT INTO `logs2` (\"col 1\", v1) VALUES (?, ?)"
+        )
+    }
+
+    #[test]
+    fn test_redact_backslash_escapes() {
+        assert_eq!(
+            redact(
+                "UPDATE users SET note = 'it\\'s secret', pass = 'p\\\\' WHERE id = 1",
+                true
+            ),
+            "UPDATE users SET note = ?, pass = ? WHERE id = ?"
+        );
+        assert_eq!(
+            redact(
+                "SELECT * FROM files WHERE path = 'C:\\' AND name = 'secret'",
+                false
+            ),
+            "SELECT * FROM files WHERE path = ? AND name = ?"
+        );
+        assert_eq!(
+            redact("SELECT E'it\\'s secret', e'\\\\', type FROM users", false),
+            "SELECT ?, ?, type FROM users"
+        )
+    }
+
+    #[test]
+    fn test_redact_dollar_quoted() {
+        assert_eq!(
+            redact(
+                "SELECT $$it's secret$$, $1, $tag$a $$ secret$tag$ FROM users",
+                false
+            ),
+            "SELECT ?, $1, ? FROM users"
+        );
+        assert_eq!(
+            redact("DO $body$ BEGIN PERFORM 'secret'; END $body$", false),
+            "DO ?"
+        );
+        assert_eq!(
+            redact("SELECT price$1 FROM items", false),
+            "SELECT price$1 FROM items"
+        );
+        assert_eq!(redact("SELECT $$unterminated secret", false), "SELECT ?")
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_trace_slow_query() {
+        let counter = Arc::new(Counter::default());
+        let _guard = tracing::subscriber::set_default(CountingSubscriber(counter.clone()));
+
+        let opts = TracingOptions {
+            slow_query_threshold: Some(Duration::from_millis(10)),
+        };
+
+        let fast = trace(&opts, "SELECT 1", false, async { Ok(vec![1]) }).await;
+        assert_eq!(fast.unwrap(), vec![1]);
+        assert_eq!(counter.slow_queries.load(Ordering::SeqCst), 0);
+
+        let slow: Result<Vec<i32>, sqlx::Error> = trace(&opts, "SELECT 1", false, async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err(sqlx::Error::RowNotFound)
+        })
+        .await;
+
+        assert!(slow.is_err());
+        assert_eq!(counter.slow_queries.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.spans.load(Ordering::SeqCst), 2)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_trace_operation() {
+        let counter = Arc::new(Counter::default());
+        let _guard = tracing::subscriber::set_default(CountingSubscriber(counter.clone()));
+
+        let opts = TracingOptions {
+            slow_query_threshold: Some(Duration::from_millis(10)),
+        };
+
+        let result: Result<(), String> = trace_operation(&opts, "migrate", async {
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            Err("dirty migration".to_string())
+        })
+        .await;
+
+        assert!(result.is_err());
+        assert_eq!(counter.slow_queries.load(Ordering::SeqCst), 1);
+        assert_eq!(counter.spans.load(Ordering::SeqCst), 1)
+    }
+}
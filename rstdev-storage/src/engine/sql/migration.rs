@@ -0,0 +1,481 @@
+//! `migration` provides a schema migration runner for all `sql` engines, built on top
+//! of `sqlx` migration supports
+//!
+//! Each migration is a versioned pair of up and down SQL scripts, loaded from a directory
+//! or embedded at compile time. All applied versions will be tracked in `_sqlx_migrations` table
+//! including for their checksums, so an applied migration that changed will be detected.
+//!
+//! While reading the status, migrating or rolling back, the runner will take a database lock, so
+//! concurrent deploys will not race with each other, and all checks before migrating or rolling
+//! back will be done while holding the lock.
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::path::Path;
+
+use sqlx::migrate::{
+    AppliedMigration, Migrate, MigrateError, Migration, MigrationType, Migrator as SqlxMigrator,
+};
+use sqlx::pool::{Pool, PoolConnection};
+use sqlx::Database;
+
+#[cfg(feature = "tracing")]
+use crate::engine::sql::instrument::{self, TracingOptions};
+use crate::types::StorageError;
+
+/// `EmbeddedMigration` is a single migration that embedded at compile time
+///
+/// Example:
+///
+/// ```rust
+/// EmbeddedMigration {
+///     version: 1,
+///     description: "create users",
+///     up: include_str!("../migrations/1_create_users.up.sql"),
+///     down: Some(include_str!("../migrations/1_create_users.down.sql")),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct EmbeddedMigration {
+    pub version: i64,
+    pub description: &'static str,
+    pub up: &'static str,
+    pub down: Option<&'static str>,
+}
+
+/// `MigrationState` is a state of a single migration compared with the database
+#[derive(Debug, Clone, PartialEq)]
+pub enum MigrationState {
+    Pending,
+    Applied,
+
+    /// `ChecksumMismatch` means the migration already applied but its script has been changed
+    ChecksumMismatch,
+}
+
+/// `MigrationStatus` is a status of a single migration returned by [`Migrator::status`]
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub reversible: bool,
+    pub state: MigrationState,
+}
+
+/// `Migrator` used to run all migrations against a pooled connection, it can be used
+/// with `MysqlDB` or `PostgresqlDB` pool connection
+///
+/// The migrations directory should follow `<VERSION>_<DESCRIPTION>.up.sql` and
+/// `<VERSION>_<DESCRIPTION>.down.sql` file names, example: `1_create_users.up.sql`
+///
+/// With `tracing` feature enabled, each operation will be traced inside a `sql.operation` span
+pub struct Migrator {
+    inner: SqlxMigrator,
+    locking: bool,
+    #[cfg(feature = "tracing")]
+    tracing: TracingOptions,
+}
+
+impl Migrator {
+    /// `from_dir` used to load all migrations from given directory
+    pub async fn from_dir(dir: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let inner = SqlxMigrator::new(dir.as_ref())
+            .await
+            .map_err(|err| StorageError::MigrationError(err.to_string()))?;
+
+        Ok(Self::from(inner))
+    }
+
+    /// `embedded` used to build the migrator from embedded migrations, the migrations
+    /// will be sorted by their versions
+    pub fn embedded(mut migrations: Vec<EmbeddedMigration>) -> Self {
+        migrations.sort_by_key(|migration| migration.version);
+
+        let mut items = Vec::new();
+        for migration in migrations {
+            let description = Cow::Borrowed(migration.description);
+            match migration.down {
+                Some(down) => {
+                    items.push(Migration::new(
+                        migration.version,
+                        description.clone(),
+                        MigrationType::ReversibleUp,
+                        Cow::Borrowed(migration.up),
+                    ));
+
+                    items.push(Migration::new(
+                        migration.version,
+                        description,
+                        MigrationType::ReversibleDown,
+                        Cow::Borrowed(down),
+                    ));
+                }
+                None => items.push(Migration::new(
+                    migration.version,
+                    description,
+                    MigrationType::Simple,
+                    Cow::Borrowed(migration.up),
+                )),
+            }
+        }
+
+        let mut inner = SqlxMigrator::DEFAULT;
+        inner.migrations = Cow::Owned(items);
+
+        Self::from(inner)
+    }
+
+    /// `with_tracing` used to set the [`TracingOptions`] used to trace all operations
+    #[cfg(feature = "tracing")]
+    pub fn with_tracing(mut self, tracing: TracingOptions) -> Self {
+        self.tracing = tracing;
+        self
+    }
+
+    /// `status` used to compare all known migrations with the applied versions
+    pub async fn status<TDB>(&self, pool: &Pool<TDB>) -> Result<Vec<MigrationStatus>, StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        let status = self.read_status(pool);
+
+        #[cfg(feature = "tracing")]
+        let status = instrument::trace_operation(&self.tracing, "migration status", status);
+
+        status.await
+    }
+
+    /// `migrate` used to apply all pending migrations, it will fail when there is an
+    /// applied migration that has been changed
+    pub async fn migrate<TDB>(&self, pool: &Pool<TDB>) -> Result<(), StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        let migrate = async {
+            let mut conn = self.lock(pool).await?;
+            let result = self.inner.run(&mut conn).await.map_err(migration_error);
+            self.unlock(conn, result).await
+        };
+
+        #[cfg(feature = "tracing")]
+        let migrate = instrument::trace_operation(&self.tracing, "migrate", migrate);
+
+        migrate.await
+    }
+
+    /// `rollback` used to revert the last `n` applied migrations, all of them must have
+    /// their down scripts
+    pub async fn rollback<TDB>(&self, pool: &Pool<TDB>, n: usize) -> Result<(), StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        let rollback = self.undo(pool, n);
+
+        #[cfg(feature = "tracing")]
+        let rollback = instrument::trace_operation(&self.tracing, "rollback", rollback);
+
+        rollback.await
+    }
+
+    async fn read_status<TDB>(&self, pool: &Pool<TDB>) -> Result<Vec<MigrationStatus>, StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        let mut conn = self.lock(pool).await?;
+        let result = self.applied(&mut conn).await;
+        let applied = self.unlock(conn, result).await?;
+
+        let status = self
+            .inner
+            .iter()
+            .filter(|migration| migration.migration_type.is_up_migration())
+            .map(|migration| {
+                let state = match applied.get(&migration.version) {
+                    Some(applied) if applied.checksum != migration.checksum => {
+                        MigrationState::ChecksumMismatch
+                    }
+                    Some(_) => MigrationState::Applied,
+                    None => MigrationState::Pending,
+                };
+
+                MigrationStatus {
+                    version: migration.version,
+                    description: migration.description.to_string(),
+                    reversible: migration.migration_type.is_reversible(),
+                    state,
+                }
+            })
+            .collect();
+
+        Ok(status)
+    }
+
+    async fn undo<TDB>(&self, pool: &Pool<TDB>, n: usize) -> Result<(), StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        if n == 0 {
+            return Ok(());
+        }
+
+        let mut conn = self.lock(pool).await?;
+        let result = self.undo_locked(&mut conn, n).await;
+        self.unlock(conn, result).await
+    }
+
+    async fn undo_locked<TDB>(
+        &self,
+        conn: &mut PoolConnection<TDB>,
+        n: usize,
+    ) -> Result<(), StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        let mut versions: Vec<i64> = self.applied(conn).await?.into_keys().collect();
+        versions.sort_unstable_by(|a, b| b.cmp(a));
+
+        for version in versions.iter().take(n) {
+            let reversible = self.inner.iter().any(|migration| {
+                migration.version == *version && migration.migration_type.is_down_migration()
+            });
+
+            if !reversible {
+                return Err(StorageError::MigrationError(format!(
+                    "migration {} is not reversible",
+                    version
+                )));
+            }
+        }
+
+        let target = versions.get(n).copied().unwrap_or(0);
+        self.inner.undo(conn, target).await.map_err(migration_error)
+    }
+
+    async fn applied<TDB>(
+        &self,
+        conn: &mut PoolConnection<TDB>,
+    ) -> Result<HashMap<i64, AppliedMigration>, StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        conn.ensure_migrations_table()
+            .await
+            .map_err(migration_error)?;
+
+        let applied = conn
+            .list_applied_migrations()
+            .await
+            .map_err(migration_error)?;
+
+        Ok(applied
+            .into_iter()
+            .map(|migration| (migration.version, migration))
+            .collect())
+    }
+
+    /// `lock` used to acquire a connection and take the migration lock on it, unless the locking
+    /// has been disabled on the `sqlx` migrator
+    async fn lock<TDB>(&self, pool: &Pool<TDB>) -> Result<PoolConnection<TDB>, StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        let mut conn = pool.acquire().await.map_err(StorageError::from)?;
+        if self.locking {
+            conn.lock().await.map_err(migration_error)?;
+        }
+
+        Ok(conn)
+    }
+
+    /// `unlock` used to release the migration lock after given result, when it failed to be
+    /// released the connection will be closed, so the lock will not be held by the pool
+    async fn unlock<TDB, T>(
+        &self,
+        mut conn: PoolConnection<TDB>,
+        result: Result<T, StorageError>,
+    ) -> Result<T, StorageError>
+    where
+        TDB: Database,
+        TDB::Connection: Migrate,
+    {
+        if !self.locking {
+            return result;
+        }
+
+        match conn.unlock().await.map_err(migration_error) {
+            Ok(_) => result,
+            Err(err) => {
+                let _ = conn.close().await;
+                result.and(Err(err))
+            }
+        }
+    }
+}
+
+fn migration_error(err: MigrateError) -> StorageError {
+    StorageError::MigrationError(err.to_string())
+}
+
+impl From<SqlxMigrator> for Migrator {
+    fn from(mut inner: SqlxMigrator) -> Self {
+        // the lock will be taken by the runner itself, so all checks before migrating or
+        // rolling back can be done while holding the same lock
+        let locking = inner.locking;
+        inner.set_locking(false);
+
+        Self {
+            inner,
+            locking,
+            #[cfg(feature = "tracing")]
+            tracing: TracingOptions::default(),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    use rst_common::with_tokio::tokio;
+    use sqlx::sqlite::{Sqlite, SqlitePoolOptions};
+
+    fn migrations() -> Vec<EmbeddedMigration> {
+        vec![
+            EmbeddedMigration {
+                version: 2,
+                description: "create posts",
+                up: "CREATE TABLE posts (id INTEGER PRIMARY KEY)",
+                down: Some("DROP TABLE posts"),
+            },
+            EmbeddedMigration {
+                version: 1,
+                description: "create users",
+                up: "CREATE TABLE users (id INTEGER PRIMARY KEY)",
+                down: Some("DROP TABLE users"),
+            },
+        ]
+    }
+
+    async fn pool() -> Pool<Sqlite> {
+        SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap()
+    }
+
+    fn states(status: Vec<MigrationStatus>) -> Vec<(i64, MigrationState)> {
+        status
+            .into_iter()
+            .map(|status| (status.version, status.state))
+            .collect()
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_migrate_and_rollback() {
+        let pool = pool().await;
+        let migrator = Migrator::embedded(migrations());
+
+        let status = migrator.status(&pool).await.unwrap();
+        assert_eq!(
+            states(status),
+            vec![(1, MigrationState::Pending), (2, MigrationState::Pending)]
+        );
+
+        assert!(migrator.migrate(&pool).await.is_ok());
+        let status = migrator.status(&pool).await.unwrap();
+        assert_eq!(
+            states(status),
+            vec![(1, MigrationState::Applied), (2, MigrationState::Applied)]
+        );
+
+        assert!(migrator.rollback(&pool, 1).await.is_ok());
+        let status = migrator.status(&pool).await.unwrap();
+        assert_eq!(
+            states(status),
+            vec![(1, MigrationState::Applied), (2, MigrationState::Pending)]
+        );
+
+        assert!(migrator.rollback(&pool, 5).await.is_ok());
+        let status = migrator.status(&pool).await.unwrap();
+        assert_eq!(
+            states(status),
+            vec![(1, MigrationState::Pending), (2, MigrationState::Pending)]
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_checksum_mismatch() {
+        let pool = pool().await;
+        assert!(Migrator::embedded(migrations())
+            .migrate(&pool)
+            .await
+            .is_ok());
+
+        let mut changed = migrations();
+        changed[1].up = "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)";
+
+        let migrator = Migrator::embedded(changed);
+        let status = migrator.status(&pool).await.unwrap();
+        assert_eq!(
+            states(status),
+            vec![
+                (1, MigrationState::ChecksumMismatch),
+                (2, MigrationState::Applied)
+            ]
+        );
+
+        assert!(migrator.migrate(&pool).await.is_err())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_rollback_not_reversible() {
+        let pool = pool().await;
+        let migrator = Migrator::embedded(vec![EmbeddedMigration {
+            version: 1,
+            description: "create users",
+            up: "CREATE TABLE users (id INTEGER PRIMARY KEY)",
+            down: None,
+        }]);
+
+        assert!(migrator.migrate(&pool).await.is_ok());
+        assert_eq!(
+            migrator.rollback(&pool, 1).await.unwrap_err(),
+            StorageError::MigrationError("migration 1 is not reversible".to_string())
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_from_dir() {
+        let dir = env::temp_dir().join(format!("rstdev-storage-migrations-{}", std::process::id()));
+        let _ = fs::create_dir_all(&dir);
+        let _ = fs::write(
+            dir.join("1_create_users.up.sql"),
+            "CREATE TABLE users (id INTEGER PRIMARY KEY)",
+        );
+        let _ = fs::write(dir.join("1_create_users.down.sql"), "DROP TABLE users");
+
+        let migrator = Migrator::from_dir(&dir).await;
+        assert!(migrator.is_ok());
+
+        let pool = pool().await;
+        let migrator = migrator.unwrap();
+        assert!(migrator.migrate(&pool).await.is_ok());
+
+        let status = migrator.status(&pool).await.unwrap();
+        assert_eq!(status[0].description, "create users");
+        assert!(status[0].reversible);
+        assert_eq!(status[0].state, MigrationState::Applied);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -1,10 +1,25 @@
 //! `sql` module provide basic implementation of `Storage` that build on top of `sqlx` library
 //!
-//! For now it only support two types of sql databases:
+//! For now it only support three types of sql databases:
 //! - Mysql
 //! - Postgres
+//! - Sqlite
 
+pub mod db;
+
+#[cfg(any(feature = "mysql", feature = "postgresql"))]
+pub mod dsn;
+
+pub mod error;
+
+#[cfg(feature = "tracing")]
+pub mod instrument;
+
+pub mod migration;
 pub mod options;
+pub mod replica;
+pub mod stats;
+pub mod transaction;
 pub mod types;
 
 #[cfg(feature = "mysql")]
@@ -12,3 +27,6 @@ pub mod mysql;
 
 #[cfg(feature = "postgresql")]
 pub mod postgresql;
+
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
@@ -1,17 +1,203 @@
 //! `options` provides an implementation basic data structure to hold database main and pooled
 //! options
 use core::time::Duration;
+use std::fmt;
 use std::marker::PhantomData;
+#[cfg(any(feature = "mysql", feature = "postgresql"))]
+use std::path::Path;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 use sqlx::pool::PoolOptions;
 use sqlx::Database;
 
 use crate::types::StorageError;
 
+/// `SslMode` used to decide how the connection should be secured
+///
+/// - [`SslMode::Disable`], never use an encrypted connection
+/// - [`SslMode::Prefer`], try an encrypted connection first and fallback to the plain one
+/// - [`SslMode::Require`], always use an encrypted connection without verifying the server
+/// - [`SslMode::VerifyCa`], verify the server certificate against the CA
+/// - [`SslMode::VerifyIdentity`], same as `VerifyCa` and also verify the server host name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SslMode {
+    Disable,
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyIdentity,
+}
+
+impl fmt::Display for SslMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            SslMode::Disable => "disable",
+            SslMode::Prefer => "prefer",
+            SslMode::Require => "require",
+            SslMode::VerifyCa => "verify-ca",
+            SslMode::VerifyIdentity => "verify-identity",
+        };
+
+        write!(f, "{}", mode)
+    }
+}
+
+/// Parse both of Postgres (`verify-full`) and Mysql (`VERIFY_IDENTITY`) mode names
+impl FromStr for SslMode {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "disable" | "disabled" => Ok(SslMode::Disable),
+            "prefer" | "preferred" => Ok(SslMode::Prefer),
+            "require" | "required" => Ok(SslMode::Require),
+            "verify-ca" => Ok(SslMode::VerifyCa),
+            "verify-full" | "verify-identity" => Ok(SslMode::VerifyIdentity),
+            _ => Err(StorageError::BuildOptionsError(format!(
+                "unknown ssl mode: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// `ConnectionMode` used to decide which connections will be built by `build_conns`
+///
+/// - [`ConnectionMode::PoolOnly`], only build the pooled connection
+/// - [`ConnectionMode::SingleOnly`], only build the single connection
+/// - [`ConnectionMode::Both`], build both of single and pooled connections, this is the default mode
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum ConnectionMode {
+    PoolOnly,
+    SingleOnly,
+    #[default]
+    Both,
+}
+
+impl ConnectionMode {
+    pub fn has_single(&self) -> bool {
+        !matches!(self, ConnectionMode::PoolOnly)
+    }
+
+    pub fn has_pool(&self) -> bool {
+        !matches!(self, ConnectionMode::SingleOnly)
+    }
+}
+
+impl fmt::Display for ConnectionMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self {
+            ConnectionMode::PoolOnly => "pool-only",
+            ConnectionMode::SingleOnly => "single-only",
+            ConnectionMode::Both => "both",
+        };
+
+        write!(f, "{}", mode)
+    }
+}
+
+impl FromStr for ConnectionMode {
+    type Err = StorageError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().replace('_', "-").as_str() {
+            "pool" | "pool-only" => Ok(ConnectionMode::PoolOnly),
+            "single" | "single-only" => Ok(ConnectionMode::SingleOnly),
+            "both" => Ok(ConnectionMode::Both),
+            _ => Err(StorageError::BuildOptionsError(format!(
+                "unknown connection mode: {}",
+                s
+            ))),
+        }
+    }
+}
+
+/// `TlsOptions` used to configure an encrypted connection
+///
+/// The `ca_cert` is used to verify the server certificate, when it's missing the system
+/// root certificates will be used. Both of `client_cert` and `client_key` must be set
+/// together to enable the client certificate authentication
+///
+/// An encrypted connection needs `tls-rustls` or `tls-native-tls` feature, without them only
+/// [`SslMode::Disable`] and [`SslMode::Prefer`] are allowed
+#[derive(Debug, Clone)]
+pub struct TlsOptions {
+    pub mode: SslMode,
+    pub ca_cert: Option<PathBuf>,
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+}
+
+impl TlsOptions {
+    /// `new` used to build options with given mode without any certificates
+    pub fn new(mode: SslMode) -> Self {
+        Self {
+            mode,
+            ca_cert: None,
+            client_cert: None,
+            client_key: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), StorageError> {
+        let certs = [
+            ("ca certificate", &self.ca_cert),
+            ("client certificate", &self.client_cert),
+            ("client key", &self.client_key),
+        ];
+
+        if self.mode == SslMode::Disable && certs.iter().any(|(_, path)| path.is_some()) {
+            return Err(StorageError::BuildOptionsError(
+                "tls certificates are set but ssl mode is disabled".to_string(),
+            ));
+        }
+
+        if self.client_cert.is_some() != self.client_key.is_some() {
+            return Err(StorageError::BuildOptionsError(
+                "client certificate and client key must be set together".to_string(),
+            ));
+        }
+
+        for (name, path) in certs {
+            match path {
+                Some(path) if path.as_os_str().is_empty() => {
+                    return Err(StorageError::BuildOptionsError(format!("{} is empty", name)))
+                }
+                Some(path) if !path.is_file() => {
+                    return Err(StorageError::BuildOptionsError(format!(
+                        "{} not found: {}",
+                        name,
+                        path.display()
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        let encrypted = matches!(
+            self.mode,
+            SslMode::Require | SslMode::VerifyCa | SslMode::VerifyIdentity
+        );
+
+        if encrypted && !cfg!(any(feature = "tls-rustls", feature = "tls-native-tls")) {
+            return Err(StorageError::BuildOptionsError(format!(
+                "ssl mode {} requires tls-rustls or tls-native-tls feature",
+                self.mode
+            )));
+        }
+
+        Ok(())
+    }
+}
+
 /// `DefaultDBOptions` used as main and primary database options
 ///
 /// This object struct also provide associated method to [`DefaultDBOptions::validate`] current
-/// given option fields
+/// given option fields. The `tls` is optional, when it's missing the engine default will be used
+///
+/// Prefer [`DefaultDBOptions::new`] over a struct literal, so a new optional field will not break
+/// the existing code
 #[derive(Debug, Clone)]
 pub struct DefaultDBOptions {
     pub host: Option<String>,
@@ -19,9 +205,37 @@ pub struct DefaultDBOptions {
     pub password: String,
     pub db: String,
     pub port: Option<u16>,
+    pub tls: Option<TlsOptions>,
 }
 
 impl DefaultDBOptions {
+    /// `new` used to build options with given credentials, all optional fields will be empty
+    pub fn new(username: String, password: String, db: String) -> Self {
+        Self {
+            host: None,
+            username,
+            password,
+            db,
+            port: None,
+            tls: None,
+        }
+    }
+
+    pub fn with_host(mut self, host: String) -> Self {
+        self.host = Some(host);
+        self
+    }
+
+    pub fn with_port(mut self, port: u16) -> Self {
+        self.port = Some(port);
+        self
+    }
+
+    pub fn with_tls(mut self, tls: TlsOptions) -> Self {
+        self.tls = Some(tls);
+        self
+    }
+
     pub fn validate(&self) -> Result<(), StorageError> {
         if self.username.is_empty() {
             return Err(StorageError::BuildOptionsError(
@@ -39,10 +253,39 @@ impl DefaultDBOptions {
             return Err(StorageError::BuildOptionsError("db is empty".to_string()));
         }
 
-        Ok(())
+        match &self.tls {
+            Some(tls) => tls.validate(),
+            None => Ok(()),
+        }
     }
 }
 
+/// `validate_socket` used to validate a Unix socket path of the Mysql and Postgres session options
+#[cfg(any(feature = "mysql", feature = "postgresql"))]
+pub(crate) fn validate_socket(socket: &Path) -> Result<(), StorageError> {
+    if socket.as_os_str().is_empty() {
+        return Err(StorageError::BuildOptionsError(
+            "socket is empty".to_string(),
+        ));
+    }
+
+    if !socket.is_absolute() {
+        return Err(StorageError::BuildOptionsError(format!(
+            "socket must be an absolute path: {}",
+            socket.display()
+        )));
+    }
+
+    Ok(())
+}
+
+/// `is_identifier` used to check a session value that will be sent to the server as it is,
+/// like a charset or a schema name
+#[cfg(any(feature = "mysql", feature = "postgresql"))]
+pub(crate) fn is_identifier(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 /// `DefaultDBPoolOptions` used to hold database pool options
 ///
 /// Not all `sqlx` pool options defined here, I only set a necessary properties, and all of them
@@ -105,3 +348,113 @@ where
         pool_opts
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn db_options(tls: Option<TlsOptions>) -> DefaultDBOptions {
+        let db_opts =
+            DefaultDBOptions::new("user".to_string(), "secret".to_string(), "app".to_string());
+
+        match tls {
+            Some(tls) => db_opts.with_tls(tls),
+            None => db_opts,
+        }
+    }
+
+    fn cert_file(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("rstdev-storage-{}-{}", std::process::id(), name));
+        let _ = fs::write(&path, "-----BEGIN CERTIFICATE-----");
+        path
+    }
+
+    fn validation_error(tls: TlsOptions) -> String {
+        db_options(Some(tls)).validate().unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_validation_tls_optional() {
+        assert!(db_options(None).validate().is_ok());
+        assert!(db_options(Some(TlsOptions::new(SslMode::Prefer)))
+            .validate()
+            .is_ok());
+
+        let db_opts =
+            DefaultDBOptions::new("user".to_string(), "secret".to_string(), "app".to_string())
+                .with_host("localhost".to_string())
+                .with_port(5432);
+        assert_eq!(db_opts.host, Some("localhost".to_string()));
+        assert_eq!(db_opts.port, Some(5432));
+        assert!(db_opts.tls.is_none())
+    }
+
+    #[cfg(not(any(feature = "tls-rustls", feature = "tls-native-tls")))]
+    #[test]
+    fn test_validation_tls_feature_disabled() {
+        assert_eq!(
+            validation_error(TlsOptions::new(SslMode::Require)),
+            "options error: ssl mode require requires tls-rustls or tls-native-tls feature"
+        );
+        assert!(validation_error(TlsOptions::new(SslMode::VerifyIdentity))
+            .contains("requires tls-rustls or tls-native-tls feature"))
+    }
+
+    #[cfg(any(feature = "tls-rustls", feature = "tls-native-tls"))]
+    #[test]
+    fn test_validation_tls_success() {
+        assert!(db_options(Some(TlsOptions::new(SslMode::Require)))
+            .validate()
+            .is_ok());
+
+        let mut tls = TlsOptions::new(SslMode::VerifyIdentity);
+        tls.ca_cert = Some(cert_file("ca.pem"));
+        tls.client_cert = Some(cert_file("client.pem"));
+        tls.client_key = Some(cert_file("client.key"));
+        assert!(db_options(Some(tls)).validate().is_ok())
+    }
+
+    #[test]
+    fn test_validation_tls_disabled_with_certs() {
+        let mut tls = TlsOptions::new(SslMode::Disable);
+        tls.ca_cert = Some(cert_file("ca.pem"));
+
+        assert!(validation_error(tls).contains("ssl mode is disabled"))
+    }
+
+    #[test]
+    fn test_validation_tls_client_cert_without_key() {
+        let mut tls = TlsOptions::new(SslMode::Require);
+        tls.client_cert = Some(cert_file("client.pem"));
+
+        assert!(validation_error(tls).contains("must be set together"))
+    }
+
+    #[test]
+    fn test_validation_tls_missing_files() {
+        let mut tls = TlsOptions::new(SslMode::VerifyCa);
+        tls.ca_cert = Some(PathBuf::new());
+        assert!(validation_error(tls.clone()).contains("ca certificate is empty"));
+
+        tls.ca_cert = Some(PathBuf::from("/not/exists/ca.pem"));
+        assert!(validation_error(tls).contains("ca certificate not found: /not/exists/ca.pem"))
+    }
+
+    #[test]
+    fn test_connection_mode_from_str() {
+        assert_eq!(
+            "pool".parse::<ConnectionMode>(),
+            Ok(ConnectionMode::PoolOnly)
+        );
+        assert_eq!(
+            "SINGLE_ONLY".parse::<ConnectionMode>(),
+            Ok(ConnectionMode::SingleOnly)
+        );
+        assert_eq!(ConnectionMode::default(), ConnectionMode::Both);
+        assert!(!ConnectionMode::PoolOnly.has_single());
+        assert!(!ConnectionMode::SingleOnly.has_pool());
+        assert!("lazy".parse::<ConnectionMode>().is_err())
+    }
+}
@@ -0,0 +1,620 @@
+//! `replica` provides a replicated storage which consists of a single primary pool and
+//! multiple read replica pools
+//!
+//! All writes should go to the primary through [`ReplicatedDB::writer`], and reads will be
+//! balanced between the healthy replicas through [`ReplicatedDB::reader`]. A replica that failed
+//! its health check will be removed from the rotation, and re-added once it can be pinged again.
+//! When there are no healthy replicas, all reads will fallback to the primary.
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use rst_common::standard::async_trait::async_trait;
+use rst_common::with_tokio::tokio::sync::Mutex as AsyncMutex;
+use sqlx::pool::Pool;
+
+use crate::engine::sql::db::{SqlDB, SqlEngine};
+use crate::registry::Lifecycle;
+use crate::types::{Pinger, Storage, StorageError};
+
+/// `Balance` used to choose a replica for each read
+///
+/// - [`Balance::RoundRobin`], choose each healthy replica in turn
+/// - [`Balance::LeastConnections`], choose a healthy replica with the least used connections
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Balance {
+    RoundRobin,
+    LeastConnections,
+}
+
+/// `Replica` keeps a replica storage behind an async lock, which is only held to rebuild or close
+/// it, while its current pool is shared with the readers
+struct Replica<E>
+where
+    E: SqlEngine,
+{
+    db: AsyncMutex<SqlDB<E>>,
+    pool: RwLock<Option<Pool<E::Database>>>,
+    healthy: AtomicBool,
+    last_error: Mutex<Option<String>>,
+}
+
+impl<E> Replica<E>
+where
+    E: SqlEngine,
+{
+    fn pool(&self) -> Option<Pool<E::Database>> {
+        self.pool
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn set_pool(&self, pool: Option<Pool<E::Database>>) {
+        *self.pool.write().unwrap_or_else(PoisonError::into_inner) = pool;
+    }
+
+    fn is_healthy(&self) -> bool {
+        self.healthy.load(Ordering::SeqCst)
+            && self
+                .pool
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .is_some()
+    }
+
+    fn set_error(&self, err: Option<&StorageError>) {
+        let mut last_error = self
+            .last_error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        *last_error = err.map(|err| err.to_string());
+    }
+
+    fn in_use(&self) -> u32 {
+        self.pool()
+            .map(|pool| pool.size().saturating_sub(pool.num_idle() as u32))
+            .unwrap_or_default()
+    }
+
+    /// `mark` used to save the result of a rebuild or a ping
+    fn mark(&self, result: Result<Pool<E::Database>, StorageError>) {
+        match result {
+            Ok(pool) => {
+                self.set_pool(Some(pool));
+                self.healthy.store(true, Ordering::SeqCst);
+                self.set_error(None);
+            }
+            Err(err) => {
+                self.healthy.store(false, Ordering::SeqCst);
+                self.set_error(Some(&err));
+            }
+        }
+    }
+}
+
+/// `ReplicatedDB` is an object that implement [`Storage`] for a primary database and its
+/// read replicas
+///
+/// Both of the primary and the replicas are [`SqlDB`] of the same engine, so each of them has its
+/// own retry policy, connection mode, metrics and tracing, example:
+///
+/// ```rust
+/// let primary = PostgresqlDB::new(DB::new(primary_opts)).with_retry(RetryPolicy::exponential(5));
+/// let replicas = vec![PostgresqlDB::new(DB::new(replica1_opts)), PostgresqlDB::new(DB::new(replica2_opts))];
+///
+/// let mut db = ReplicatedDB::new(primary, replicas).with_balance(Balance::LeastConnections);
+/// db.build_conns().await?;
+///
+/// sqlx::query("INSERT INTO users (name) VALUES ('a')").execute(db.writer()?).await?;
+/// sqlx::query("SELECT * FROM users").fetch_all(&db.reader()?).await?;
+/// ```
+///
+/// Reads are going through the replica pools, so a replica must not use `ConnectionMode::SingleOnly`.
+pub struct ReplicatedDB<E>
+where
+    E: SqlEngine,
+{
+    // the primary is only used through `&mut self`, the lock is only used to share it between threads
+    primary: AsyncMutex<SqlDB<E>>,
+    primary_conn: Option<Pool<E::Database>>,
+    replicas: Arc<Vec<Replica<E>>>,
+    balance: Balance,
+    next: AtomicUsize,
+}
+
+impl<E> ReplicatedDB<E>
+where
+    E: SqlEngine,
+{
+    pub fn new(primary: SqlDB<E>, replicas: Vec<SqlDB<E>>) -> Self {
+        let replicas = replicas
+            .into_iter()
+            .map(|db| Replica {
+                db: AsyncMutex::new(db),
+                pool: RwLock::new(None),
+                healthy: AtomicBool::new(false),
+                last_error: Mutex::new(None),
+            })
+            .collect();
+
+        Self {
+            primary: AsyncMutex::new(primary),
+            primary_conn: None,
+            replicas: Arc::new(replicas),
+            balance: Balance::RoundRobin,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// `with_balance` used to set the replica selection, by default it will be [`Balance::RoundRobin`]
+    pub fn with_balance(mut self, balance: Balance) -> Self {
+        self.balance = balance;
+        self
+    }
+
+    /// `build_conns` used to build the primary and all replica connections, using their own
+    /// [`SqlDB::build_conns`]
+    ///
+    /// Only the primary failure will be returned, a replica that failed to connect will be
+    /// left out of the rotation until it's rebuilt by [`Storage::ping`] or [`Storage::reconnect`],
+    /// its error can be read from [`ReplicatedDB::replica_errors`]
+    pub async fn build_conns(&mut self) -> Result<(), StorageError> {
+        let primary = self.primary.get_mut();
+        primary.build_conns().await?;
+        self.primary_conn = primary.pool_conn.clone();
+
+        for replica in self.replicas.iter() {
+            let mut db = replica.db.lock().await;
+            let result = db.build_conns().await.and_then(|_| replica_pool(&db));
+            replica.mark(result);
+        }
+
+        Ok(())
+    }
+
+    /// `writer` used to get the primary pool
+    pub fn writer(&self) -> Result<&Pool<E::Database>, StorageError> {
+        self.primary_conn
+            .as_ref()
+            .ok_or(StorageError::ConnectionError(
+                "missing primary pool connection".to_string(),
+            ))
+    }
+
+    /// `reader` used to get a healthy replica pool, or the primary pool when there are
+    /// no healthy replicas
+    ///
+    /// A replica pool may be rebuilt at any time, so it's returned as a cloned handle
+    pub fn reader(&self) -> Result<Pool<E::Database>, StorageError> {
+        let replica = self.select().and_then(|index| self.replicas[index].pool());
+
+        match replica {
+            Some(pool) => Ok(pool),
+            None => self.writer().cloned(),
+        }
+    }
+
+    /// `session` used to start a [`Session`] for a single caller, used to read its own writes
+    pub fn session(&self) -> Session<'_, E> {
+        Session {
+            db: self,
+            pinned: AtomicBool::new(false),
+        }
+    }
+
+    /// `healthy_replicas` used to count the replicas that are currently in the rotation
+    pub fn healthy_replicas(&self) -> usize {
+        self.replicas
+            .iter()
+            .filter(|replica| replica.is_healthy())
+            .count()
+    }
+
+    /// `replica_errors` used to get the last connect or ping error of each replica, in the same
+    /// order as given replicas, a healthy replica will not have any error
+    pub fn replica_errors(&self) -> Vec<Option<String>> {
+        self.replicas
+            .iter()
+            .map(|replica| {
+                replica
+                    .last_error
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner)
+                    .clone()
+            })
+            .collect()
+    }
+
+    /// `check_replicas` used to ping all replicas, a failed replica will be removed from the rotation
+    /// and a recovered replica will be added back
+    pub async fn check_replicas(&self) {
+        check_replicas(&self.replicas).await
+    }
+
+    /// `rebuild_replicas` used to rebuild all replica pools that missing or closed, the reads will
+    /// not wait for it, a replica that failed to connect will stay out of the rotation
+    pub async fn rebuild_replicas(&self) {
+        rebuild_replicas(&self.replicas).await
+    }
+
+    fn select(&self) -> Option<usize> {
+        let healthy: Vec<usize> = (0..self.replicas.len())
+            .filter(|index| self.replicas[*index].is_healthy())
+            .collect();
+
+        if healthy.is_empty() {
+            return None;
+        }
+
+        match self.balance {
+            Balance::RoundRobin => {
+                let next = self.next.fetch_add(1, Ordering::SeqCst);
+                Some(healthy[next % healthy.len()])
+            }
+            Balance::LeastConnections => healthy
+                .into_iter()
+                .min_by_key(|index| self.replicas[*index].in_use()),
+        }
+    }
+}
+
+/// `Session` used to pin a caller to the primary after its first write, so all of its next reads
+/// will see its own writes even when the replicas are lagging
+pub struct Session<'a, E>
+where
+    E: SqlEngine,
+{
+    db: &'a ReplicatedDB<E>,
+    pinned: AtomicBool,
+}
+
+impl<'a, E> Session<'a, E>
+where
+    E: SqlEngine,
+{
+    /// `writer` used to get the primary pool, and pin this session to the primary
+    pub fn writer(&self) -> Result<&'a Pool<E::Database>, StorageError> {
+        self.pin();
+        self.db.writer()
+    }
+
+    /// `reader` used to get the primary pool when this session is pinned, or a replica pool
+    pub fn reader(&self) -> Result<Pool<E::Database>, StorageError> {
+        match self.is_pinned() {
+            true => self.db.writer().cloned(),
+            false => self.db.reader(),
+        }
+    }
+
+    /// `pin` used to pin this session to the primary without any writes
+    pub fn pin(&self) {
+        self.pinned.store(true, Ordering::SeqCst)
+    }
+
+    pub fn is_pinned(&self) -> bool {
+        self.pinned.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl<E> Storage for ReplicatedDB<E>
+where
+    E: SqlEngine,
+{
+    type Instance = Arc<Self>;
+
+    fn get_instance(self) -> Self::Instance {
+        Arc::new(self)
+    }
+
+    /// `ping` will rebuild the missing replica pools and check all replicas, it will only fail when
+    /// the primary cannot be pinged
+    ///
+    /// When it's used with the `HealthMonitor`, a failed replica will be re-added on the next
+    /// interval after it's available again. Each rebuild waits up to the replica pool
+    /// `acquire_timeout`, so the monitor `timeout` should be longer than it.
+    async fn ping(&mut self) -> Result<(), StorageError> {
+        rebuild_replicas(&self.replicas).await;
+        check_replicas(&self.replicas).await;
+        self.primary.get_mut().ping().await
+    }
+
+    /// `pinger` will ping the primary pool and rebuild and check the replicas the same as
+    /// [`Storage::ping`], without borrowing this storage
+    fn pinger(&self) -> Option<Box<dyn Pinger>> {
+        let primary = self.primary.try_lock().ok()?.pinger()?;
+        Some(Box::new(ReplicaPinger {
+            primary,
+            replicas: self.replicas.clone(),
+        }))
+    }
+
+    /// `reconnect` will reconnect the primary and rebuild all replica pools that missing or closed
+    async fn reconnect(&mut self) -> Result<(), StorageError> {
+        let primary = self.primary.get_mut();
+        primary.reconnect().await?;
+        self.primary_conn = primary.pool_conn.clone();
+
+        rebuild_replicas(&self.replicas).await;
+        check_replicas(&self.replicas).await;
+        Ok(())
+    }
+
+    /// `close` will close the primary and all replicas, the first failure will be returned after
+    /// all of them are closed
+    async fn close(&mut self) -> Result<(), StorageError> {
+        self.primary_conn = None;
+        let mut result = self.primary.get_mut().close().await;
+
+        for replica in self.replicas.iter() {
+            replica.healthy.store(false, Ordering::SeqCst);
+            replica.set_pool(None);
+
+            let closed = replica.db.lock().await.close().await;
+            if result.is_ok() {
+                result = closed;
+            }
+        }
+
+        result
+    }
+}
+
+#[async_trait]
+impl<E> Lifecycle for ReplicatedDB<E>
+where
+    E: SqlEngine,
+{
+    async fn start(&mut self) -> Result<(), StorageError> {
+        self.build_conns().await
+    }
+
+    async fn stop(&mut self) -> Result<(), StorageError> {
+        self.close().await
+    }
+}
+
+/// `ReplicaPinger` is a [`Pinger`] of the primary pool and the shared replicas
+struct ReplicaPinger<E>
+where
+    E: SqlEngine,
+{
+    primary: Box<dyn Pinger>,
+    replicas: Arc<Vec<Replica<E>>>,
+}
+
+#[async_trait]
+impl<E> Pinger for ReplicaPinger<E>
+where
+    E: SqlEngine,
+{
+    async fn ping(&self) -> Result<(), StorageError> {
+        rebuild_replicas(&self.replicas).await;
+        check_replicas(&self.replicas).await;
+        self.primary.ping().await
+    }
+}
+
+async fn rebuild_replicas<E>(replicas: &[Replica<E>])
+where
+    E: SqlEngine,
+{
+    for replica in replicas.iter() {
+        if replica.pool().is_some_and(|pool| !pool.is_closed()) {
+            continue;
+        }
+
+        let mut db = replica.db.lock().await;
+        let result = db.reconnect().await.and_then(|_| replica_pool(&db));
+        if result.is_err() {
+            replica.set_pool(None);
+        }
+
+        replica.mark(result);
+    }
+}
+
+async fn check_replicas<E>(replicas: &[Replica<E>])
+where
+    E: SqlEngine,
+{
+    for replica in replicas.iter() {
+        let pinger = match replica.db.lock().await.pinger() {
+            Some(pinger) => pinger,
+            None => continue,
+        };
+
+        let result = pinger.ping().await;
+        replica.healthy.store(result.is_ok(), Ordering::SeqCst);
+        replica.set_error(result.as_ref().err());
+    }
+}
+
+fn replica_pool<E>(db: &SqlDB<E>) -> Result<Pool<E::Database>, StorageError>
+where
+    E: SqlEngine,
+{
+    db.pool_conn.clone().ok_or(StorageError::ConnectionError(
+        "missing replica pool connection".to_string(),
+    ))
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    use rst_common::with_tokio::tokio;
+
+    use crate::engine::sql::options::{ConnectionMode, DefaultDBPoolOptions};
+    use crate::engine::sql::sqlite::db::{SqliteDB, SqliteEngine, DB};
+    use crate::engine::sql::sqlite::options::{Options, SqliteDBOptions};
+
+    fn pool_options() -> DefaultDBPoolOptions {
+        DefaultDBPoolOptions {
+            max_conns: Some(2),
+            min_conns: None,
+            idle_duration: None,
+            lifetime_duration: None,
+            acquire_timeout: None,
+        }
+    }
+
+    fn sqlite(db_opts: SqliteDBOptions) -> SqliteDB {
+        let opts = Options::new(db_opts, pool_options()).unwrap();
+        SqliteDB::new(DB::new(opts)).with_connection_mode(ConnectionMode::PoolOnly)
+    }
+
+    async fn build_db(replicas: usize, balance: Balance) -> ReplicatedDB<SqliteEngine> {
+        let replicas = (0..replicas)
+            .map(|_| sqlite(SqliteDBOptions::memory()))
+            .collect();
+
+        let mut db =
+            ReplicatedDB::new(sqlite(SqliteDBOptions::memory()), replicas).with_balance(balance);
+        assert!(db.build_conns().await.is_ok());
+
+        db
+    }
+
+    fn replica_pool(db: &ReplicatedDB<SqliteEngine>, index: usize) -> Pool<sqlx::Sqlite> {
+        db.replicas[index].pool().unwrap()
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_round_robin() {
+        let db = build_db(3, Balance::RoundRobin).await;
+        assert_eq!(db.healthy_replicas(), 3);
+
+        let selected: Vec<Option<usize>> = (0..4).map(|_| db.select()).collect();
+        assert_eq!(selected, vec![Some(0), Some(1), Some(2), Some(0)]);
+        assert!(db.reader().is_ok());
+        assert!(db.writer().is_ok())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_least_connections() {
+        let db = build_db(2, Balance::LeastConnections).await;
+
+        let busy = replica_pool(&db, 0).acquire().await;
+        assert!(busy.is_ok());
+        assert_eq!(db.select(), Some(1));
+
+        drop(busy);
+        let busy = replica_pool(&db, 1).acquire().await;
+        assert!(busy.is_ok());
+        assert_eq!(db.select(), Some(0))
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_replica_removed_and_readded() {
+        let mut db = build_db(2, Balance::RoundRobin).await;
+
+        replica_pool(&db, 0).close().await;
+        db.check_replicas().await;
+        assert_eq!(db.healthy_replicas(), 1);
+        assert_eq!(db.select(), Some(1));
+        assert_eq!(db.select(), Some(1));
+        assert!(db.replica_errors()[0].is_some());
+
+        assert!(db.ping().await.is_ok());
+        assert_eq!(db.healthy_replicas(), 2);
+        assert_eq!(db.replica_errors(), vec![None, None]);
+
+        replica_pool(&db, 1).close().await;
+        assert!(db.reconnect().await.is_ok());
+        assert_eq!(db.healthy_replicas(), 2);
+
+        assert!(db.close().await.is_ok());
+        assert_eq!(db.healthy_replicas(), 0);
+        assert!(db.reader().is_err())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_pinger_rebuild_replicas() {
+        let db = build_db(2, Balance::RoundRobin).await;
+        let pinger = db.pinger().unwrap();
+
+        replica_pool(&db, 0).close().await;
+        db.check_replicas().await;
+        assert_eq!(db.healthy_replicas(), 1);
+
+        // the reads are not blocked while the replicas are rebuilt through the pinger
+        let (pinged, reader) = tokio::join!(pinger.ping(), async { db.reader() });
+        assert!(pinged.is_ok());
+        assert!(reader.is_ok());
+        assert_eq!(db.healthy_replicas(), 2)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_replica_connect_error() {
+        let path = std::env::temp_dir().join(format!("rstdev-replica-{}.db", std::process::id()));
+        let mut replica_opts = SqliteDBOptions::file(path.clone());
+        replica_opts.create_if_missing = false;
+
+        let mut db = ReplicatedDB::new(
+            sqlite(SqliteDBOptions::memory()),
+            vec![sqlite(replica_opts)],
+        );
+        assert!(db.build_conns().await.is_ok());
+        assert_eq!(db.healthy_replicas(), 0);
+        assert!(db.replica_errors()[0]
+            .as_ref()
+            .is_some_and(|err| err.starts_with("storage connection rejected")));
+
+        assert!(std::fs::File::create(&path).is_ok());
+        assert!(db.ping().await.is_ok());
+        assert_eq!(db.healthy_replicas(), 1);
+        assert_eq!(db.replica_errors(), vec![None]);
+
+        assert!(db.close().await.is_ok());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_replica_without_pool() {
+        let replica =
+            sqlite(SqliteDBOptions::memory()).with_connection_mode(ConnectionMode::SingleOnly);
+
+        let mut db = ReplicatedDB::new(sqlite(SqliteDBOptions::memory()), vec![replica]);
+        assert!(db.build_conns().await.is_ok());
+        assert_eq!(db.healthy_replicas(), 0);
+        assert_eq!(
+            db.replica_errors(),
+            vec![Some(
+                "storage connection error: missing replica pool connection".to_string()
+            )]
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_fallback_to_primary() {
+        let db = build_db(0, Balance::RoundRobin).await;
+        assert_eq!(db.select(), None);
+        assert!(db.reader().is_ok())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_session_read_your_writes() {
+        let db = build_db(1, Balance::RoundRobin).await;
+
+        let session = db.session();
+        assert!(!session.is_pinned());
+
+        let created = sqlx::query("CREATE TABLE users (id INTEGER PRIMARY KEY)")
+            .execute(session.writer().unwrap())
+            .await;
+        assert!(created.is_ok());
+        assert!(session.is_pinned());
+
+        // each in-memory database is separated, so only the primary has the table
+        let count: Result<(i64,), sqlx::Error> = sqlx::query_as("SELECT COUNT(*) FROM users")
+            .fetch_one(&session.reader().unwrap())
+            .await;
+        assert_eq!(count.unwrap().0, 0);
+
+        let count: Result<(i64,), sqlx::Error> = sqlx::query_as("SELECT COUNT(*) FROM users")
+            .fetch_one(&db.reader().unwrap())
+            .await;
+        assert!(count.is_err())
+    }
+}
@@ -0,0 +1,114 @@
+//! `db` is a module that provide Sqlite implementation of [`SqlEngine`], all of its connection
+//! behaviors are shared through [`SqlDB`]
+use std::sync::Arc;
+
+use rst_common::with_tokio::tokio::sync::Mutex;
+use sqlx::sqlite::Sqlite;
+
+use crate::engine::sql::db::{self, SqlDB, SqlEngine};
+use crate::engine::sql::sqlite::options::Options;
+use crate::engine::sql::transaction::IsolationPlacement;
+
+/// `SqliteEngine` used to define Sqlite specific types for [`SqlDB`]
+///
+/// Unlike other engines, the single `SqliteConnection` is `Send` but not `Sync`, since its worker
+/// holds a raw `sqlite3` handle, so `Arc<SqliteDB>` cannot be an `Instance` and it will be wrapped
+/// in a [`Mutex`] instead. Sqlite also doesn't support isolation levels, a transaction will fail
+/// if it's set
+pub struct SqliteEngine;
+
+impl SqlEngine for SqliteEngine {
+    type Database = Sqlite;
+    type Options = Options;
+    type Instance = Arc<Mutex<SqliteDB>>;
+
+    const ISOLATION: IsolationPlacement = IsolationPlacement::Unsupported;
+    const BACKSLASH_ESCAPES: bool = false;
+
+    fn instance(db: SqlDB<Self>) -> Self::Instance {
+        Arc::new(Mutex::new(db))
+    }
+}
+
+/// `DB` will depends to [`Options`] to setup it's database connection and also it's instance
+pub type DB = db::DB<SqliteEngine>;
+
+/// `SqliteDB` is an object that implement [`crate::types::Storage`], this object will depends to [`DB`]
+/// to build it's connection types, a common or pooled connections
+pub type SqliteDB = SqlDB<SqliteEngine>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rst_common::with_tokio::tokio;
+
+    use crate::engine::sql::options::DefaultDBPoolOptions;
+    use crate::engine::sql::sqlite::options::SqliteDBOptions;
+    use crate::engine::sql::transaction::{IsolationLevel, TransactionOptions};
+    use crate::types::{Storage, StorageError};
+
+    fn build_db() -> SqliteDB {
+        let opts = Options::new(
+            SqliteDBOptions::memory(),
+            DefaultDBPoolOptions {
+                max_conns: Some(2),
+                min_conns: None,
+                idle_duration: None,
+                lifetime_duration: None,
+                acquire_timeout: None,
+            },
+        );
+
+        SqliteDB::new(DB::new(opts.unwrap()))
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_build_conns_in_memory() {
+        let mut sqlite = build_db();
+        assert!(sqlite.build_conns().await.is_ok());
+        assert!(sqlite.ping().await.is_ok());
+
+        let pool = sqlite.pool_conn.clone().unwrap();
+        let created = sqlx::query("CREATE TABLE settings (key TEXT PRIMARY KEY, value TEXT)")
+            .execute(&pool)
+            .await;
+        assert!(created.is_ok());
+
+        let inserted = sqlx::query("INSERT INTO settings (key, value) VALUES ('a', 'b')")
+            .execute(&pool)
+            .await;
+        assert!(inserted.is_ok());
+
+        let value: Result<(String,), sqlx::Error> =
+            sqlx::query_as("SELECT value FROM settings WHERE key = 'a'")
+                .fetch_one(sqlite.single_conn.as_mut().unwrap())
+                .await;
+        assert_eq!(value.unwrap().0, "b");
+
+        assert!(sqlite.close().await.is_ok());
+        assert!(sqlite.ping().await.is_err())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_with_transaction_isolation_unsupported() {
+        let mut sqlite = build_db();
+        assert!(sqlite.build_conns().await.is_ok());
+
+        let opts = TransactionOptions {
+            isolation: Some(IsolationLevel::Serializable),
+            ..TransactionOptions::default()
+        };
+
+        let result: Result<(), StorageError> = sqlite
+            .with_transaction_opts(opts, |_| Box::pin(async move { Ok(()) }))
+            .await;
+
+        assert_eq!(
+            result,
+            Err(StorageError::BuildOptionsError(
+                "isolation level is not supported".to_string()
+            ))
+        )
+    }
+}
@@ -0,0 +1,4 @@
+//! `sqlite` is a module that provide an implementation of `Storage` and also
+//! sql trait behaviors
+pub mod db;
+pub mod options;
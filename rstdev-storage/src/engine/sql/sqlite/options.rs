@@ -0,0 +1,278 @@
+//! `options` used to manage all necessary options to setup Sqlite database instance
+//! and connection
+use core::time::Duration;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use sqlx::pool::PoolOptions;
+use sqlx::sqlite::{Sqlite, SqliteConnectOptions, SqliteJournalMode, SqliteSynchronous};
+
+use crate::types::StorageError;
+
+use crate::engine::sql::options::{DefaultDBPoolOptions, DefaultDBPoolOptionsBuilder};
+use crate::engine::sql::types::{SqlxOptionsBuilder, SqlxPoolOptionsBuilder};
+
+/// `SqliteMode` used to choose where the database will be stored
+///
+/// - [`SqliteMode::File`], a physical database file
+/// - [`SqliteMode::Memory`], an in-memory database that shared between the single and pooled connections
+#[derive(Debug, Clone, PartialEq)]
+pub enum SqliteMode {
+    File(PathBuf),
+    Memory,
+}
+
+/// `JournalMode` is a list of available Sqlite journal modes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+
+impl From<JournalMode> for SqliteJournalMode {
+    fn from(mode: JournalMode) -> Self {
+        match mode {
+            JournalMode::Delete => SqliteJournalMode::Delete,
+            JournalMode::Truncate => SqliteJournalMode::Truncate,
+            JournalMode::Persist => SqliteJournalMode::Persist,
+            JournalMode::Memory => SqliteJournalMode::Memory,
+            JournalMode::Wal => SqliteJournalMode::Wal,
+            JournalMode::Off => SqliteJournalMode::Off,
+        }
+    }
+}
+
+/// `Synchronous` is a list of available Sqlite synchronous modes, commonly used
+/// `Normal` when using [`JournalMode::Wal`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl From<Synchronous> for SqliteSynchronous {
+    fn from(synchronous: Synchronous) -> Self {
+        match synchronous {
+            Synchronous::Off => SqliteSynchronous::Off,
+            Synchronous::Normal => SqliteSynchronous::Normal,
+            Synchronous::Full => SqliteSynchronous::Full,
+            Synchronous::Extra => SqliteSynchronous::Extra,
+        }
+    }
+}
+
+/// `SqliteDBOptions` used as main Sqlite database options
+///
+/// Since Sqlite doesn't need any servers, it doesn't have any host or credentials like
+/// `DefaultDBOptions`. All optional fields will use the Sqlite (or `sqlx`) default values
+/// unless we also set them
+#[derive(Debug, Clone)]
+pub struct SqliteDBOptions {
+    pub mode: SqliteMode,
+    pub create_if_missing: bool,
+    pub read_only: bool,
+    pub journal_mode: Option<JournalMode>,
+    pub synchronous: Option<Synchronous>,
+    pub busy_timeout: Option<Duration>,
+    pub foreign_keys: Option<bool>,
+}
+
+impl SqliteDBOptions {
+    /// `file` used to build options for a database file, the file will be created if it's missing
+    pub fn file(path: PathBuf) -> Self {
+        Self {
+            mode: SqliteMode::File(path),
+            create_if_missing: true,
+            read_only: false,
+            journal_mode: None,
+            synchronous: None,
+            busy_timeout: None,
+            foreign_keys: None,
+        }
+    }
+
+    /// `memory` used to build options for an in-memory database
+    pub fn memory() -> Self {
+        Self {
+            mode: SqliteMode::Memory,
+            create_if_missing: false,
+            read_only: false,
+            journal_mode: None,
+            synchronous: None,
+            busy_timeout: None,
+            foreign_keys: None,
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), StorageError> {
+        match &self.mode {
+            SqliteMode::File(path) if path.as_os_str().is_empty() => {
+                return Err(StorageError::BuildOptionsError(
+                    "filename is empty".to_string(),
+                ))
+            }
+            SqliteMode::Memory if self.journal_mode == Some(JournalMode::Wal) => {
+                return Err(StorageError::BuildOptionsError(
+                    "wal journal mode is not available for in-memory database".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        if self.read_only && self.create_if_missing {
+            return Err(StorageError::BuildOptionsError(
+                "unable to create a missing read only database".to_string(),
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// `Options` will hold two kind of options, a main database options and pooled options
+///
+/// This object also implement [`SqlxOptionsBuilder`] to build common database options
+/// and also [`SqlxPoolOptionsBuilder`] to build pooled options.
+///
+/// For the pooled options, it will be used [`DefaultDBPoolOptionsBuilder`]
+pub struct Options {
+    base_opts: SqliteConnectOptions,
+    db_opts: SqliteDBOptions,
+    pool_opts: DefaultDBPoolOptions,
+}
+
+impl Options {
+    pub fn new(
+        db_opts: SqliteDBOptions,
+        pool_opts: DefaultDBPoolOptions,
+    ) -> Result<Self, StorageError> {
+        db_opts.validate()?;
+
+        // an in-memory database will get its unique shared name once, so both of single
+        // and pooled connections will connect to the same database
+        let base_opts = match &db_opts.mode {
+            SqliteMode::File(path) => SqliteConnectOptions::new().filename(path),
+            SqliteMode::Memory => SqliteConnectOptions::from_str("sqlite::memory:")
+                .map_err(|err| StorageError::BuildOptionsError(err.to_string()))?,
+        };
+
+        Ok(Self {
+            base_opts,
+            db_opts,
+            pool_opts,
+        })
+    }
+}
+
+impl SqlxOptionsBuilder for Options {
+    type SqlxOptionType = SqliteConnectOptions;
+
+    fn common_options(&self) -> Self::SqlxOptionType {
+        let mut sqlite_opts = self
+            .base_opts
+            .clone()
+            .create_if_missing(self.db_opts.create_if_missing)
+            .read_only(self.db_opts.read_only);
+
+        if let Some(journal_mode) = self.db_opts.journal_mode {
+            sqlite_opts = sqlite_opts.journal_mode(journal_mode.into());
+        }
+
+        if let Some(synchronous) = self.db_opts.synchronous {
+            sqlite_opts = sqlite_opts.synchronous(synchronous.into());
+        }
+
+        if let Some(busy_timeout) = self.db_opts.busy_timeout {
+            sqlite_opts = sqlite_opts.busy_timeout(busy_timeout);
+        }
+
+        match self.db_opts.foreign_keys {
+            Some(foreign_keys) => sqlite_opts.foreign_keys(foreign_keys),
+            None => sqlite_opts,
+        }
+    }
+}
+
+impl SqlxPoolOptionsBuilder for Options {
+    type SqlxDatabase = Sqlite;
+
+    fn pool_options(&self) -> PoolOptions<Self::SqlxDatabase> {
+        DefaultDBPoolOptionsBuilder::<Self::SqlxDatabase>::new(self.pool_opts.to_owned()).build()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validation_success() {
+        assert!(SqliteDBOptions::file(PathBuf::from("./db.sqlite"))
+            .validate()
+            .is_ok());
+        assert!(SqliteDBOptions::memory().validate().is_ok())
+    }
+
+    #[test]
+    fn test_validation_error_empty_filename() {
+        let opts = SqliteDBOptions::file(PathBuf::new()).validate();
+
+        assert!(opts.is_err());
+        assert!(opts.unwrap_err().to_string().contains("filename is empty"))
+    }
+
+    #[test]
+    fn test_validation_error_memory_wal() {
+        let mut opts = SqliteDBOptions::memory();
+        opts.journal_mode = Some(JournalMode::Wal);
+
+        let validation = opts.validate();
+        assert!(validation.is_err());
+        assert!(validation
+            .unwrap_err()
+            .to_string()
+            .contains("wal journal mode is not available"))
+    }
+
+    #[test]
+    fn test_validation_error_read_only() {
+        let mut opts = SqliteDBOptions::file(PathBuf::from("./db.sqlite"));
+        opts.read_only = true;
+
+        let validation = opts.validate();
+        assert!(validation.is_err());
+        assert!(validation
+            .unwrap_err()
+            .to_string()
+            .contains("unable to create a missing read only database"))
+    }
+
+    #[test]
+    fn test_common_options() {
+        let mut db_opts = SqliteDBOptions::file(PathBuf::from("./db.sqlite"));
+        db_opts.busy_timeout = Some(Duration::from_secs(1));
+
+        let opts = Options::new(
+            db_opts,
+            DefaultDBPoolOptions {
+                max_conns: None,
+                min_conns: None,
+                idle_duration: None,
+                lifetime_duration: None,
+                acquire_timeout: None,
+            },
+        );
+
+        assert!(opts.is_ok());
+        assert_eq!(
+            opts.unwrap().common_options().get_filename().as_ref(),
+            PathBuf::from("./db.sqlite").as_path()
+        )
+    }
+}
@@ -0,0 +1,259 @@
+//! `stats` provides pool statistics and a pluggable [`MetricsSink`] for all `sql` storages
+//!
+//! The pool size and idle connections will be read from the pool itself, while the other counters
+//! are collected when the storage opens a new connection or acquires a connection from its pool.
+//! Only acquisitions through the storage, like `SqlDB::acquire` or `with_transaction`, will be
+//! recorded, `sqlx` doesn't provide any acquire hook, so executing a query directly against the
+//! pool, like `query(..).fetch_all(&pool)`, will not be recorded.
+use core::time::Duration;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+
+use sqlx::pool::{Pool, PoolConnection};
+use sqlx::Database;
+
+/// `WAIT_BUCKETS` is a list of upper bounds used by the acquire wait time histogram
+pub const WAIT_BUCKETS: [Duration; 10] = [
+    Duration::from_millis(1),
+    Duration::from_millis(5),
+    Duration::from_millis(10),
+    Duration::from_millis(25),
+    Duration::from_millis(50),
+    Duration::from_millis(100),
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_secs(5),
+];
+
+/// `PoolEvent` is a single pool event sent to a [`MetricsSink`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum PoolEvent {
+    /// `Acquired` contains how long the caller waited for the connection
+    Acquired(Duration),
+
+    /// `AcquireTimedOut` contains how long the caller waited before timed out
+    AcquireTimedOut(Duration),
+
+    ConnectionOpened,
+}
+
+/// `MetricsSink` used to forward pool events to any metrics library, like Prometheus
+/// or OpenTelemetry
+///
+/// Example:
+///
+/// ```rust
+/// struct Prometheus;
+///
+/// impl MetricsSink for Prometheus {
+///     fn record(&self, event: &PoolEvent) {
+///         if let PoolEvent::Acquired(wait) = event {
+///             ACQUIRE_WAIT.observe(wait.as_secs_f64());
+///         }
+///     }
+/// }
+/// ```
+pub trait MetricsSink: Send + Sync {
+    fn record(&self, event: &PoolEvent);
+}
+
+/// `WaitHistogram` is a snapshot of the acquire wait time histogram
+///
+/// Each `counts` item is a number of acquisitions waited up to its `bounds` item, and the last
+/// item is a number of acquisitions waited longer than the last bound
+#[derive(Debug, Clone, PartialEq)]
+pub struct WaitHistogram {
+    pub bounds: Vec<Duration>,
+    pub counts: Vec<u64>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+/// `PoolStats` is a snapshot of the pool statistics
+///
+/// - `size`, `idle` and `in_use`, the current connections of the pool
+/// - `max_conns`, the pool max connections, used to check the pool saturation
+/// - `acquire_wait`, a histogram of the time waited to acquire a connection
+/// - `acquire_timeouts`, a number of acquisitions that timed out
+/// - `opened`, a number of connections opened by the pool
+/// - `closed`, a number of connections closed by the pool, like after its idle timeout or max
+///   lifetime reached
+///
+/// Both of `acquire_wait` and `acquire_timeouts` only count the acquisitions through the storage,
+/// like `SqlDB::acquire` or `with_transaction`, a query executed directly against the pool will
+/// not be counted. Since `sqlx` doesn't provide any hook when the pool closes a connection,
+/// `closed` is derived from the opened connections that no longer in the pool
+#[derive(Debug, Clone, PartialEq)]
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: u32,
+    pub in_use: u32,
+    pub max_conns: u32,
+    pub acquire_wait: WaitHistogram,
+    pub acquire_timeouts: u64,
+    pub opened: u64,
+    pub closed: u64,
+}
+
+/// `PoolMetrics` used to collect the pool counters, shared between the storage and its pool
+pub struct PoolMetrics {
+    wait_counts: [AtomicU64; WAIT_BUCKETS.len() + 1],
+    wait_sum_micros: AtomicU64,
+    acquire_timeouts: AtomicU64,
+    opened: AtomicU64,
+    sink: Option<Arc<dyn MetricsSink>>,
+}
+
+impl PoolMetrics {
+    pub fn new(sink: Option<Arc<dyn MetricsSink>>) -> Self {
+        Self {
+            wait_counts: Default::default(),
+            wait_sum_micros: AtomicU64::new(0),
+            acquire_timeouts: AtomicU64::new(0),
+            opened: AtomicU64::new(0),
+            sink,
+        }
+    }
+
+    pub fn record(&self, event: PoolEvent) {
+        match &event {
+            PoolEvent::Acquired(wait) => {
+                let bucket = WAIT_BUCKETS
+                    .iter()
+                    .position(|bound| wait <= bound)
+                    .unwrap_or(WAIT_BUCKETS.len());
+
+                self.wait_counts[bucket].fetch_add(1, Ordering::Relaxed);
+                self.wait_sum_micros
+                    .fetch_add(wait.as_micros() as u64, Ordering::Relaxed);
+            }
+            PoolEvent::AcquireTimedOut(_) => {
+                self.acquire_timeouts.fetch_add(1, Ordering::Relaxed);
+            }
+            PoolEvent::ConnectionOpened => {
+                self.opened.fetch_add(1, Ordering::Relaxed);
+            }
+        }
+
+        if let Some(sink) = &self.sink {
+            sink.record(&event)
+        }
+    }
+
+    /// `snapshot` used to build [`PoolStats`] from current counters and given pool
+    pub fn snapshot<TDB>(&self, pool: &Pool<TDB>) -> PoolStats
+    where
+        TDB: Database,
+    {
+        let size = pool.size();
+        let idle = pool.num_idle() as u32;
+        let opened = self.opened.load(Ordering::Relaxed);
+        let counts: Vec<u64> = self
+            .wait_counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+
+        PoolStats {
+            size,
+            idle,
+            in_use: size.saturating_sub(idle),
+            max_conns: pool.options().get_max_connections(),
+            acquire_wait: WaitHistogram {
+                bounds: WAIT_BUCKETS.to_vec(),
+                count: counts.iter().sum(),
+                counts,
+                sum: Duration::from_micros(self.wait_sum_micros.load(Ordering::Relaxed)),
+            },
+            acquire_timeouts: self.acquire_timeouts.load(Ordering::Relaxed),
+            opened,
+            closed: opened.saturating_sub(size as u64),
+        }
+    }
+}
+
+/// `acquire` used to acquire a connection from given pool and record its wait time
+pub(crate) async fn acquire<TDB>(
+    pool: &Pool<TDB>,
+    metrics: &PoolMetrics,
+) -> Result<PoolConnection<TDB>, sqlx::Error>
+where
+    TDB: Database,
+{
+    let started = Instant::now();
+    let conn = pool.acquire().await;
+    match &conn {
+        Ok(_) => metrics.record(PoolEvent::Acquired(started.elapsed())),
+        Err(sqlx::Error::PoolTimedOut) => {
+            metrics.record(PoolEvent::AcquireTimedOut(started.elapsed()))
+        }
+        Err(_) => {}
+    }
+
+    conn
+}
+
+impl Default for PoolMetrics {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct MemorySink {
+        events: Mutex<Vec<PoolEvent>>,
+    }
+
+    impl MetricsSink for MemorySink {
+        fn record(&self, event: &PoolEvent) {
+            self.events.lock().unwrap().push(event.clone())
+        }
+    }
+
+    #[test]
+    fn test_record_wait_histogram() {
+        let metrics = PoolMetrics::default();
+        metrics.record(PoolEvent::Acquired(Duration::from_micros(500)));
+        metrics.record(PoolEvent::Acquired(Duration::from_millis(1)));
+        metrics.record(PoolEvent::Acquired(Duration::from_millis(30)));
+        metrics.record(PoolEvent::Acquired(Duration::from_secs(6)));
+
+        let counts: Vec<u64> = metrics
+            .wait_counts
+            .iter()
+            .map(|count| count.load(Ordering::Relaxed))
+            .collect();
+
+        assert_eq!(counts, vec![2, 0, 0, 0, 1, 0, 0, 0, 0, 0, 1]);
+        assert_eq!(metrics.wait_sum_micros.load(Ordering::Relaxed), 6_031_500)
+    }
+
+    #[test]
+    fn test_record_sink() {
+        let sink = Arc::new(MemorySink {
+            events: Mutex::new(Vec::new()),
+        });
+
+        let metrics = PoolMetrics::new(Some(sink.clone()));
+        metrics.record(PoolEvent::ConnectionOpened);
+        metrics.record(PoolEvent::AcquireTimedOut(Duration::from_secs(1)));
+
+        assert_eq!(metrics.opened.load(Ordering::Relaxed), 1);
+        assert_eq!(metrics.acquire_timeouts.load(Ordering::Relaxed), 1);
+
+        let events = sink.events.lock().unwrap().clone();
+        assert_eq!(
+            events,
+            vec![
+                PoolEvent::ConnectionOpened,
+                PoolEvent::AcquireTimedOut(Duration::from_secs(1))
+            ]
+        )
+    }
+}
@@ -0,0 +1,210 @@
+//! `transaction` provides a shared implementation of the `with_transaction` helper used by
+//! all `sql` engines
+//!
+//! The helper will begin a transaction, give it to a callback and then commit it when the callback
+//! succeed or rollback it when the callback failed. A failed transaction caused by a retryable error,
+//! like a deadlock or a serialization failure, will be retried with an exponential backoff.
+use core::future::Future;
+use core::time::Duration;
+use std::pin::Pin;
+
+use rst_common::with_tokio::tokio::time::sleep;
+use sqlx::pool::Pool;
+use sqlx::{Connection, Database, Executor, Transaction};
+
+use crate::engine::sql::error;
+use crate::engine::sql::stats::{self, PoolMetrics};
+use crate::types::StorageError;
+
+/// `TransactionFuture` is a future returned by the `with_transaction` callback
+///
+/// Since the callback borrows its transaction, the future must be boxed, example:
+///
+/// ```rust
+/// db.with_transaction(|tx| Box::pin(async move {
+///     sqlx::query("UPDATE accounts SET balance = balance - 10 WHERE id = 1")
+///         .execute(&mut **tx)
+///         .await?;
+///
+///     Ok(())
+/// }))
+/// .await?;
+/// ```
+pub type TransactionFuture<'t, T> =
+    Pin<Box<dyn Future<Output = Result<T, sqlx::Error>> + Send + 't>>;
+
+/// `IsolationLevel` is a list of standard SQL transaction isolation levels
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IsolationLevel {
+    ReadUncommitted,
+    ReadCommitted,
+    RepeatableRead,
+    Serializable,
+}
+
+impl IsolationLevel {
+    pub fn as_sql(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadUncommitted => "READ UNCOMMITTED",
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+/// `TransactionOptions` used to configure the isolation level and the retry behavior
+///
+/// A retryable transaction will be retried at most `max_retries` times, each retry will wait
+/// for a backoff duration starting from `initial_backoff` and doubled on each retry until
+/// it reaches `max_backoff`
+#[derive(Debug, Clone)]
+pub struct TransactionOptions {
+    pub isolation: Option<IsolationLevel>,
+    pub max_retries: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for TransactionOptions {
+    fn default() -> Self {
+        Self {
+            isolation: None,
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+}
+
+impl TransactionOptions {
+    fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry);
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// `IsolationPlacement` used to decide when the isolation level statement should be executed,
+/// since each engine has its own rule
+pub enum IsolationPlacement {
+    /// `BeforeBegin` will set the isolation level for the next transaction, used by Mysql
+    BeforeBegin,
+
+    /// `AfterBegin` will set the isolation level for current transaction, used by Postgres
+    AfterBegin,
+
+    /// `Unsupported` used by an engine that doesn't support isolation levels, like Sqlite
+    Unsupported,
+}
+
+/// `is_conflict` used to check if given error caused by a transient transaction conflict, which is
+/// a deadlock or a serialization failure classified by [`error::classify`]
+///
+/// Unlike [`StorageError::is_retryable`], a lost connection or a timeout is not a conflict, since
+/// the transaction callback should not be retried on them
+pub fn is_conflict(err: &sqlx::Error) -> bool {
+    matches!(
+        error::classify(err),
+        StorageError::DeadlockError(_) | StorageError::SerializationError(_)
+    )
+}
+
+pub(crate) async fn run<TDB, F, T>(
+    pool: Option<&Pool<TDB>>,
+    metrics: &PoolMetrics,
+    opts: TransactionOptions,
+    placement: IsolationPlacement,
+    mut callback: F,
+) -> Result<T, StorageError>
+where
+    TDB: Database,
+    for<'c> &'c mut TDB::Connection: Executor<'c, Database = TDB>,
+    F: for<'t> FnMut(&'t mut Transaction<'_, TDB>) -> TransactionFuture<'t, T>,
+{
+    let pool = pool.ok_or(StorageError::ConnectionError(
+        "missing pool connection".to_string(),
+    ))?;
+
+    if opts.isolation.is_some() && matches!(placement, IsolationPlacement::Unsupported) {
+        return Err(StorageError::BuildOptionsError(
+            "isolation level is not supported".to_string(),
+        ));
+    }
+
+    let mut retry = 0;
+    loop {
+        match attempt(pool, metrics, &opts, &placement, &mut callback).await {
+            Err(err) if is_conflict(&err) && retry < opts.max_retries => {
+                sleep(opts.backoff(retry)).await;
+                retry += 1;
+            }
+            result => return result.map_err(StorageError::from),
+        }
+    }
+}
+
+async fn attempt<TDB, F, T>(
+    pool: &Pool<TDB>,
+    metrics: &PoolMetrics,
+    opts: &TransactionOptions,
+    placement: &IsolationPlacement,
+    callback: &mut F,
+) -> Result<T, sqlx::Error>
+where
+    TDB: Database,
+    for<'c> &'c mut TDB::Connection: Executor<'c, Database = TDB>,
+    F: for<'t> FnMut(&'t mut Transaction<'_, TDB>) -> TransactionFuture<'t, T>,
+{
+    let isolation = opts
+        .isolation
+        .map(|level| format!("SET TRANSACTION ISOLATION LEVEL {}", level.as_sql()));
+
+    let mut conn = stats::acquire(pool, metrics).await?;
+    if let (Some(sql), IsolationPlacement::BeforeBegin) = (&isolation, placement) {
+        (&mut *conn).execute(sql.as_str()).await?;
+    }
+
+    let mut tx = conn.begin().await?;
+    if let (Some(sql), IsolationPlacement::AfterBegin) = (&isolation, placement) {
+        (&mut *tx).execute(sql.as_str()).await?;
+    }
+
+    match callback(&mut tx).await {
+        Ok(out) => {
+            tx.commit().await?;
+            Ok(out)
+        }
+        Err(err) => {
+            let _ = tx.rollback().await;
+            Err(err)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        let opts = TransactionOptions {
+            isolation: None,
+            max_retries: 5,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_millis(300),
+        };
+
+        assert_eq!(opts.backoff(0), Duration::from_millis(100));
+        assert_eq!(opts.backoff(1), Duration::from_millis(200));
+        assert_eq!(opts.backoff(2), Duration::from_millis(300));
+        assert_eq!(opts.backoff(40), Duration::from_millis(300))
+    }
+
+    #[test]
+    fn test_is_conflict_non_database_error() {
+        assert!(!is_conflict(&sqlx::Error::RowNotFound));
+        assert!(!is_conflict(&sqlx::Error::PoolTimedOut))
+    }
+}
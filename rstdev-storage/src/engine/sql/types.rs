@@ -8,6 +8,10 @@
 //! - [`SqlxPoolOptionsBuilder`] used as trait behavior to build pool options for a pool database connection
 //! - [`SqlxConnectionBuilder`] used as a trait behavior to build database connection
 
+use core::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
 use sqlx::pool::{Pool, PoolOptions};
 use sqlx::Connection;
 use sqlx::Database;
@@ -26,6 +30,16 @@ pub trait SqlxOptionsBuilder {
     fn common_options(&self) -> Self::SqlxOptionType;
 }
 
+/// `SessionHook` is a callback used to initialize the session of a newly opened connection, for
+/// the session options that cannot be set through the `sqlx` connect options
+pub type SessionHook<TDB> = Arc<
+    dyn for<'c> Fn(
+            &'c mut <TDB as Database>::Connection,
+        ) -> Pin<Box<dyn Future<Output = Result<(), sqlx::Error>> + Send + 'c>>
+        + Send
+        + Sync,
+>;
+
 /// `SqlxPoolOptionsBuilder` used to build an option for a pool database connection
 ///
 /// A pool database connection build to maintain multiple database connection, and in `sqlx`
@@ -35,6 +49,11 @@ pub trait SqlxPoolOptionsBuilder {
     type SqlxDatabase: Database;
 
     fn pool_options(&self) -> PoolOptions<Self::SqlxDatabase>;
+
+    /// `session_hook` will be called on each new single or pooled connection, it's optional
+    fn session_hook(&self) -> Option<SessionHook<Self::SqlxDatabase>> {
+        None
+    }
 }
 
 /// `SqlxConnectionBuilder` used to build database connections which consists of two type of connection
@@ -0,0 +1,474 @@
+//! `health` module provides a background health monitor for any [`Storage`] implementations
+//!
+//! The monitor will ping its storage on an interval, and try to rebuild the connection using
+//! [`Storage::reconnect`] when the ping failed. The last result will be saved as a [`HealthStatus`]
+//! which can be read by the readiness probes.
+use core::time::Duration;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Instant, SystemTime};
+
+use rst_common::with_tokio::tokio::sync::Mutex;
+use rst_common::with_tokio::tokio::task::JoinHandle;
+use rst_common::with_tokio::tokio::time::{self, timeout};
+
+use crate::types::{Storage, StorageError};
+
+/// `HealthState` is a summary of the storage health
+///
+/// - [`HealthState::Healthy`], the storage pinged successfully within the latency threshold
+/// - [`HealthState::Degraded`], the storage is available but it's slow or it just reconnected
+/// - [`HealthState::Down`], the storage is unavailable even after reconnected
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HealthState {
+    Healthy,
+    Degraded,
+    Down,
+}
+
+/// `HealthStatus` is a result of the last health check
+#[derive(Debug, Clone, PartialEq)]
+pub struct HealthStatus {
+    pub state: HealthState,
+    pub last_error: Option<String>,
+    pub latency: Option<Duration>,
+    pub checked_at: Option<SystemTime>,
+}
+
+impl HealthStatus {
+    /// `is_ready` used to check if the storage can be used, a degraded storage still can be used
+    pub fn is_ready(&self) -> bool {
+        self.state != HealthState::Down
+    }
+}
+
+impl Default for HealthStatus {
+    fn default() -> Self {
+        Self {
+            state: HealthState::Down,
+            last_error: None,
+            latency: None,
+            checked_at: None,
+        }
+    }
+}
+
+/// `HealthOptions` used to configure the health monitor behaviors
+///
+/// - `interval`, a duration between each health check
+/// - `timeout`, a maximum duration of each ping and reconnect
+/// - `degraded_latency`, a ping latency threshold before the storage considered degraded
+#[derive(Debug, Clone)]
+pub struct HealthOptions {
+    pub interval: Duration,
+    pub timeout: Duration,
+    pub degraded_latency: Duration,
+}
+
+impl Default for HealthOptions {
+    fn default() -> Self {
+        Self {
+            interval: Duration::from_secs(10),
+            timeout: Duration::from_secs(5),
+            degraded_latency: Duration::from_secs(1),
+        }
+    }
+}
+
+/// `HealthMonitor` used to check the health of a shared storage
+///
+/// Since both of [`Storage::ping`] and [`Storage::reconnect`] need a mutable access, the storage
+/// must be shared using a `Mutex`. When the storage provides a [`Storage::pinger`], like a pooled
+/// `SqlDB`, it will be pinged without holding the lock, so the lock is only held to reconnect it.
+///
+/// A monitored storage should not be converted using [`Storage::get_instance`], its `Arc<Self>`
+/// instance cannot be borrowed mutably, so the monitor cannot rebuild its connection. Use
+/// [`HealthMonitor::from_storage`] instead, and share the storage through [`HealthMonitor::storage`].
+///
+/// Example:
+///
+/// ```rust
+/// let monitor = HealthMonitor::from_storage(mysql, HealthOptions::default());
+/// let storage = monitor.storage();
+/// let handle = monitor.spawn();
+///
+/// if handle.status().is_ready() {
+///     let conn = storage.lock().await.acquire().await?;
+/// }
+/// ```
+pub struct HealthMonitor<S>
+where
+    S: Storage + Send + 'static,
+{
+    storage: Arc<Mutex<S>>,
+    status: Arc<RwLock<HealthStatus>>,
+    opts: HealthOptions,
+}
+
+impl<S> HealthMonitor<S>
+where
+    S: Storage + Send + 'static,
+{
+    pub fn new(storage: Arc<Mutex<S>>, opts: HealthOptions) -> Self {
+        Self {
+            storage,
+            status: Arc::new(RwLock::new(HealthStatus::default())),
+            opts,
+        }
+    }
+
+    /// `from_storage` used to monitor an owned storage, wrapped in a new shared `Mutex`
+    pub fn from_storage(storage: S, opts: HealthOptions) -> Self {
+        Self::new(Arc::new(Mutex::new(storage)), opts)
+    }
+
+    /// `storage` used to get the shared storage, it should be taken before [`HealthMonitor::spawn`]
+    pub fn storage(&self) -> Arc<Mutex<S>> {
+        self.storage.clone()
+    }
+
+    /// `status` used to get the last health check result
+    pub fn status(&self) -> HealthStatus {
+        read_status(&self.status)
+    }
+
+    /// `check` used to run a single health check, when the ping failed it will try to rebuild
+    /// the storage connection and ping it again
+    pub async fn check(&self) -> HealthStatus {
+        let status = match self.ping().await {
+            Ok(latency) if latency > self.opts.degraded_latency => HealthStatus {
+                state: HealthState::Degraded,
+                last_error: None,
+                latency: Some(latency),
+                checked_at: Some(SystemTime::now()),
+            },
+            Ok(latency) => HealthStatus {
+                state: HealthState::Healthy,
+                last_error: None,
+                latency: Some(latency),
+                checked_at: Some(SystemTime::now()),
+            },
+            Err(err) => self.recover(err).await,
+        };
+
+        *self.status.write().unwrap_or_else(PoisonError::into_inner) = status.clone();
+
+        status
+    }
+
+    /// `spawn` used to run the health check in a background task on each interval
+    pub fn spawn(self) -> HealthHandle {
+        let status = self.status.clone();
+        let task = rst_common::with_tokio::tokio::spawn(async move {
+            let mut interval = time::interval(self.opts.interval);
+            loop {
+                interval.tick().await;
+                self.check().await;
+            }
+        });
+
+        HealthHandle { status, task }
+    }
+
+    async fn recover(&self, err: StorageError) -> HealthStatus {
+        let reconnected = {
+            let mut storage = self.storage.lock().await;
+            match timeout(self.opts.timeout, storage.reconnect()).await {
+                Ok(result) => result,
+                Err(_) => Err(StorageError::ConnectionError(
+                    "reconnect timed out".to_string(),
+                )),
+            }
+        };
+
+        let result = match reconnected {
+            Ok(_) => self.ping().await,
+            Err(reconnect_err) => Err(reconnect_err),
+        };
+
+        match result {
+            Ok(latency) => HealthStatus {
+                state: HealthState::Degraded,
+                last_error: Some(err.to_string()),
+                latency: Some(latency),
+                checked_at: Some(SystemTime::now()),
+            },
+            Err(err) => HealthStatus {
+                state: HealthState::Down,
+                last_error: Some(err.to_string()),
+                latency: None,
+                checked_at: Some(SystemTime::now()),
+            },
+        }
+    }
+
+    /// `ping` used to ping the storage [`Storage::pinger`] without holding the lock, or the storage
+    /// itself when it has no pinger
+    async fn ping(&self) -> Result<Duration, StorageError> {
+        let pinger = self.storage.lock().await.pinger();
+        let result = match pinger {
+            Some(pinger) => {
+                let started = Instant::now();
+                timeout(self.opts.timeout, pinger.ping())
+                    .await
+                    .map(|result| result.map(|_| started.elapsed()))
+            }
+            None => {
+                let mut storage = self.storage.lock().await;
+                let started = Instant::now();
+                timeout(self.opts.timeout, storage.ping())
+                    .await
+                    .map(|result| result.map(|_| started.elapsed()))
+            }
+        };
+
+        match result {
+            Ok(result) => result,
+            Err(_) => Err(StorageError::PingError("ping timed out".to_string())),
+        }
+    }
+}
+
+/// `HealthHandle` is a handle of a running health monitor task
+pub struct HealthHandle {
+    status: Arc<RwLock<HealthStatus>>,
+    task: JoinHandle<()>,
+}
+
+impl HealthHandle {
+    /// `status` used to get the last health check result
+    pub fn status(&self) -> HealthStatus {
+        read_status(&self.status)
+    }
+
+    /// `stop` used to stop the health monitor task
+    pub fn stop(self) {
+        self.task.abort()
+    }
+}
+
+fn read_status(status: &RwLock<HealthStatus>) -> HealthStatus {
+    status
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .clone()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    use rst_common::standard::async_trait::async_trait;
+    use rst_common::with_tokio::tokio;
+
+    use crate::types::Pinger;
+
+    struct FakeStorage {
+        alive: Arc<AtomicBool>,
+        can_reconnect: bool,
+        detached: bool,
+        delay: Duration,
+        reconnected: u32,
+    }
+
+    impl FakeStorage {
+        fn new(alive: bool, can_reconnect: bool) -> Self {
+            Self {
+                alive: Arc::new(AtomicBool::new(alive)),
+                can_reconnect,
+                detached: false,
+                delay: Duration::ZERO,
+                reconnected: 0,
+            }
+        }
+    }
+
+    struct FakePinger {
+        alive: Arc<AtomicBool>,
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl Pinger for FakePinger {
+        async fn ping(&self) -> Result<(), StorageError> {
+            time::sleep(self.delay).await;
+            if !self.alive.load(Ordering::SeqCst) {
+                return Err(StorageError::PingError("connection lost".to_string()));
+            }
+
+            Ok(())
+        }
+    }
+
+    #[async_trait]
+    impl Storage for FakeStorage {
+        type Instance = Arc<Self>;
+
+        fn get_instance(self) -> Self::Instance {
+            Arc::new(self)
+        }
+
+        async fn ping(&mut self) -> Result<(), StorageError> {
+            FakePinger {
+                alive: self.alive.clone(),
+                delay: self.delay,
+            }
+            .ping()
+            .await
+        }
+
+        fn pinger(&self) -> Option<Box<dyn Pinger>> {
+            match self.detached {
+                true => Some(Box::new(FakePinger {
+                    alive: self.alive.clone(),
+                    delay: self.delay,
+                })),
+                false => None,
+            }
+        }
+
+        async fn reconnect(&mut self) -> Result<(), StorageError> {
+            self.reconnected += 1;
+            if !self.can_reconnect {
+                return Err(StorageError::ConnectionError(
+                    "connection refused".to_string(),
+                ));
+            }
+
+            self.alive.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    fn options() -> HealthOptions {
+        HealthOptions {
+            interval: Duration::from_millis(10),
+            timeout: Duration::from_millis(100),
+            degraded_latency: Duration::from_millis(50),
+        }
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_check_healthy() {
+        let monitor = HealthMonitor::new(
+            Arc::new(Mutex::new(FakeStorage::new(true, true))),
+            options(),
+        );
+        assert_eq!(monitor.status().state, HealthState::Down);
+
+        let status = monitor.check().await;
+        assert_eq!(status.state, HealthState::Healthy);
+        assert!(status.latency.is_some());
+        assert!(status.last_error.is_none());
+        assert_eq!(monitor.status(), status)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_check_degraded_latency() {
+        let mut storage = FakeStorage::new(true, true);
+        storage.delay = Duration::from_millis(60);
+
+        let monitor = HealthMonitor::new(Arc::new(Mutex::new(storage)), options());
+        let status = monitor.check().await;
+        assert_eq!(status.state, HealthState::Degraded);
+        assert!(status.is_ready())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_check_reconnected() {
+        let storage = Arc::new(Mutex::new(FakeStorage::new(false, true)));
+        let monitor = HealthMonitor::new(storage.clone(), options());
+
+        let status = monitor.check().await;
+        assert_eq!(status.state, HealthState::Degraded);
+        assert_eq!(
+            status.last_error,
+            Some("storage ping error: connection lost".to_string())
+        );
+        assert_eq!(storage.lock().await.reconnected, 1);
+
+        let status = monitor.check().await;
+        assert_eq!(status.state, HealthState::Healthy)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_check_down() {
+        let monitor = HealthMonitor::new(
+            Arc::new(Mutex::new(FakeStorage::new(false, false))),
+            options(),
+        );
+
+        let status = monitor.check().await;
+        assert_eq!(status.state, HealthState::Down);
+        assert!(!status.is_ready());
+        assert_eq!(
+            status.last_error,
+            Some("storage connection error: connection refused".to_string())
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_check_pinger_without_lock() {
+        let mut storage = FakeStorage::new(false, true);
+        storage.detached = true;
+        storage.delay = Duration::from_millis(20);
+
+        let storage = Arc::new(Mutex::new(storage));
+        let monitor = HealthMonitor::new(storage.clone(), options());
+
+        let locked = async {
+            time::sleep(Duration::from_millis(10)).await;
+            storage.try_lock().is_ok()
+        };
+
+        let (status, locked) = tokio::join!(monitor.check(), locked);
+        assert_eq!(status.state, HealthState::Degraded);
+        assert_eq!(storage.lock().await.reconnected, 1);
+        assert!(locked)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_check_poisoned_status() {
+        let monitor = HealthMonitor::from_storage(FakeStorage::new(true, true), options());
+        let status = monitor.status.clone();
+        let _ = std::thread::spawn(move || {
+            let _guard = status.write().unwrap();
+            panic!("poison the status")
+        })
+        .join();
+
+        let status = monitor.check().await;
+        assert_eq!(status.state, HealthState::Healthy);
+        assert_eq!(monitor.status(), status)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_check_ping_timeout() {
+        let mut storage = FakeStorage::new(true, false);
+        storage.delay = Duration::from_millis(200);
+
+        let monitor = HealthMonitor::new(Arc::new(Mutex::new(storage)), options());
+        let status = monitor.check().await;
+        assert_eq!(status.state, HealthState::Down)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio", start_paused = true)]
+    async fn test_spawn() {
+        let monitor = HealthMonitor::from_storage(FakeStorage::new(false, true), options());
+        let storage = monitor.storage();
+        let handle = monitor.spawn();
+
+        // the clock is paused, it only advances once the monitor task is idle, so each sleep
+        // will wait for the check of each tick to be completed
+        time::sleep(Duration::from_millis(1)).await;
+        assert_eq!(handle.status().state, HealthState::Degraded);
+        assert_eq!(storage.lock().await.reconnected, 1);
+
+        time::sleep(options().interval).await;
+        assert_eq!(handle.status().state, HealthState::Healthy);
+        assert_eq!(storage.lock().await.reconnected, 1);
+
+        handle.stop()
+    }
+}
@@ -1,4 +1,7 @@
 #![doc = include_str!("../README.md")]
 
 pub mod engine;
+pub mod health;
+pub mod registry;
+pub mod retry;
 pub mod types;
@@ -0,0 +1,505 @@
+//! `registry` module provides a [`Registry`] used to own multiple named storages
+//!
+//! All registered storages will be started in their dependency order, and stopped in the reverse
+//! order on shutdown. Each storage will be started or stopped within its own timeout, and all
+//! failures will be aggregated into a single [`StorageError::RegistryError`].
+use core::time::Duration;
+use std::collections::HashSet;
+use std::fmt;
+use std::sync::Arc;
+
+use rst_common::standard::async_trait::async_trait;
+use rst_common::with_tokio::tokio::sync::Mutex;
+use rst_common::with_tokio::tokio::time::timeout;
+
+use crate::types::StorageError;
+
+/// `Lifecycle` used by the [`Registry`] to start and stop a storage
+///
+/// Since each storage has its own way to build its connections, like `build_conns` on the `sql`
+/// storages, this trait used as a common abstraction for all of them
+#[async_trait]
+pub trait Lifecycle: Send {
+    async fn start(&mut self) -> Result<(), StorageError>;
+    async fn stop(&mut self) -> Result<(), StorageError>;
+}
+
+/// `StorageFailure` is a single failed storage, including for its name
+#[derive(Debug, PartialEq)]
+pub struct StorageFailure {
+    pub name: String,
+    pub error: StorageError,
+}
+
+impl fmt::Display for StorageFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.error)
+    }
+}
+
+/// `RegistryOptions` used to configure the timeout of each storage
+///
+/// - `start_timeout`, a maximum duration to start a single storage
+/// - `stop_timeout`, a maximum duration to stop a single storage
+#[derive(Debug, Clone)]
+pub struct RegistryOptions {
+    pub start_timeout: Duration,
+    pub stop_timeout: Duration,
+}
+
+impl Default for RegistryOptions {
+    fn default() -> Self {
+        Self {
+            start_timeout: Duration::from_secs(30),
+            stop_timeout: Duration::from_secs(10),
+        }
+    }
+}
+
+struct Entry {
+    name: String,
+    depends_on: Vec<String>,
+    storage: Arc<Mutex<dyn Lifecycle>>,
+}
+
+/// `Registry` used to own multiple named storages, example:
+///
+/// ```rust
+/// let mut registry = Registry::new(RegistryOptions::default());
+/// let mysql = registry.register("mysql", mysql, &[])?;
+/// let cache = registry.register("cache", rocksdb, &["mysql"])?;
+///
+/// registry.start().await?;
+///
+/// // ...
+///
+/// registry.shutdown().await?;
+/// ```
+pub struct Registry {
+    opts: RegistryOptions,
+    entries: Vec<Entry>,
+    started: Vec<usize>,
+}
+
+impl Registry {
+    pub fn new(opts: RegistryOptions) -> Self {
+        Self {
+            opts,
+            entries: Vec::new(),
+            started: Vec::new(),
+        }
+    }
+
+    /// `register` used to add a named storage which depends on other registered storages
+    ///
+    /// The storage will be moved into the registry, and a shared handle will be returned so
+    /// it still can be used by the caller
+    pub fn register<S>(
+        &mut self,
+        name: &str,
+        storage: S,
+        depends_on: &[&str],
+    ) -> Result<Arc<Mutex<S>>, StorageError>
+    where
+        S: Lifecycle + 'static,
+    {
+        if self.entries.iter().any(|entry| entry.name == name) {
+            return Err(StorageError::BuildOptionsError(format!(
+                "storage {} already registered",
+                name
+            )));
+        }
+
+        let handle = Arc::new(Mutex::new(storage));
+        self.entries.push(Entry {
+            name: name.to_string(),
+            depends_on: depends_on.iter().map(|dep| dep.to_string()).collect(),
+            storage: handle.clone(),
+        });
+
+        Ok(handle)
+    }
+
+    /// `names` used to get all registered storage names in their registration order
+    pub fn names(&self) -> Vec<&str> {
+        self.entries
+            .iter()
+            .map(|entry| entry.name.as_str())
+            .collect()
+    }
+
+    /// `start` used to start all storages in their dependency order
+    ///
+    /// When a storage failed to start or timed out, it will be stopped to release its partially
+    /// built connections, then all started storages will be stopped in the reverse order
+    pub async fn start(&mut self) -> Result<(), StorageError> {
+        for index in self.order()? {
+            if self.started.contains(&index) {
+                continue;
+            }
+
+            let entry = &self.entries[index];
+            let started = {
+                let mut storage = entry.storage.lock().await;
+                match timeout(self.opts.start_timeout, storage.start()).await {
+                    Ok(result) => result,
+                    Err(_) => Err(StorageError::ConnectionError(format!(
+                        "start timed out after {:?}",
+                        self.opts.start_timeout
+                    ))),
+                }
+            };
+
+            if let Err(error) = started {
+                let mut failures = vec![StorageFailure {
+                    name: entry.name.clone(),
+                    error,
+                }];
+
+                if let Err(failure) = self.stop_entry(index).await {
+                    failures.push(failure);
+                }
+
+                failures.extend(self.stop_all().await);
+                return Err(StorageError::RegistryError(failures));
+            }
+
+            self.started.push(index);
+        }
+
+        Ok(())
+    }
+
+    /// `shutdown` used to stop all started storages in the reverse order, a failed storage
+    /// will not prevent the others to be stopped
+    pub async fn shutdown(&mut self) -> Result<(), StorageError> {
+        let failures = self.stop_all().await;
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(StorageError::RegistryError(failures)),
+        }
+    }
+
+    async fn stop_all(&mut self) -> Vec<StorageFailure> {
+        let mut failures = Vec::new();
+        while let Some(index) = self.started.pop() {
+            if let Err(failure) = self.stop_entry(index).await {
+                failures.push(failure);
+            }
+        }
+
+        failures
+    }
+
+    async fn stop_entry(&self, index: usize) -> Result<(), StorageFailure> {
+        let entry = &self.entries[index];
+        let mut storage = entry.storage.lock().await;
+        let stopped = match timeout(self.opts.stop_timeout, storage.stop()).await {
+            Ok(result) => result,
+            Err(_) => Err(StorageError::ConnectionError(format!(
+                "stop timed out after {:?}",
+                self.opts.stop_timeout
+            ))),
+        };
+
+        stopped.map_err(|error| StorageFailure {
+            name: entry.name.clone(),
+            error,
+        })
+    }
+
+    /// `order` used to sort all storages by their dependencies, storages without any dependencies
+    /// between them will keep their registration order
+    fn order(&self) -> Result<Vec<usize>, StorageError> {
+        for entry in self.entries.iter() {
+            for dep in entry.depends_on.iter() {
+                if !self.entries.iter().any(|other| other.name == *dep) {
+                    return Err(StorageError::BuildOptionsError(format!(
+                        "storage {} depends on unknown storage {}",
+                        entry.name, dep
+                    )));
+                }
+            }
+        }
+
+        let mut ordered: Vec<usize> = Vec::new();
+        let mut resolved: HashSet<&str> = HashSet::new();
+        while ordered.len() < self.entries.len() {
+            let next = self.entries.iter().enumerate().position(|(index, entry)| {
+                !ordered.contains(&index)
+                    && entry
+                        .depends_on
+                        .iter()
+                        .all(|dep| resolved.contains(dep.as_str()))
+            });
+
+            match next {
+                Some(index) => {
+                    ordered.push(index);
+                    resolved.insert(self.entries[index].name.as_str());
+                }
+                None => {
+                    return Err(StorageError::BuildOptionsError(
+                        "storage dependencies contain a cycle".to_string(),
+                    ))
+                }
+            }
+        }
+
+        Ok(ordered)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex as StdMutex;
+
+    use rst_common::with_tokio::tokio;
+    use rst_common::with_tokio::tokio::time::sleep;
+
+    type Events = Arc<StdMutex<Vec<String>>>;
+
+    struct FakeStorage {
+        name: &'static str,
+        events: Events,
+        fail_start: bool,
+        fail_stop: bool,
+        start_delay: Duration,
+        stop_delay: Duration,
+    }
+
+    impl FakeStorage {
+        fn new(name: &'static str, events: &Events) -> Self {
+            Self {
+                name,
+                events: events.clone(),
+                fail_start: false,
+                fail_stop: false,
+                start_delay: Duration::ZERO,
+                stop_delay: Duration::ZERO,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Lifecycle for FakeStorage {
+        async fn start(&mut self) -> Result<(), StorageError> {
+            sleep(self.start_delay).await;
+            if self.fail_start {
+                return Err(StorageError::ConnectionError("refused".to_string()));
+            }
+
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("start {}", self.name));
+            Ok(())
+        }
+
+        async fn stop(&mut self) -> Result<(), StorageError> {
+            sleep(self.stop_delay).await;
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("stop {}", self.name));
+
+            if self.fail_stop {
+                return Err(StorageError::ConnectionError("broken".to_string()));
+            }
+
+            Ok(())
+        }
+    }
+
+    fn options() -> RegistryOptions {
+        RegistryOptions {
+            start_timeout: Duration::from_millis(50),
+            stop_timeout: Duration::from_millis(50),
+        }
+    }
+
+    fn events(events: &Events) -> Vec<String> {
+        events.lock().unwrap().clone()
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_start_and_shutdown_in_order() {
+        let log = Events::default();
+        let mut registry = Registry::new(options());
+        assert!(registry
+            .register(
+                "cache",
+                FakeStorage::new("cache", &log),
+                &["mysql", "postgres"]
+            )
+            .is_ok());
+        assert!(registry
+            .register("postgres", FakeStorage::new("postgres", &log), &[])
+            .is_ok());
+        assert!(registry
+            .register("mysql", FakeStorage::new("mysql", &log), &["postgres"])
+            .is_ok());
+
+        assert!(registry.start().await.is_ok());
+        assert!(registry.shutdown().await.is_ok());
+        assert_eq!(
+            events(&log),
+            vec![
+                "start postgres",
+                "start mysql",
+                "start cache",
+                "stop cache",
+                "stop mysql",
+                "stop postgres"
+            ]
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_start_failed_rollback() {
+        let log = Events::default();
+        let mut failed = FakeStorage::new("cache", &log);
+        failed.fail_start = true;
+
+        let mut registry = Registry::new(options());
+        assert!(registry
+            .register("mysql", FakeStorage::new("mysql", &log), &[])
+            .is_ok());
+        assert!(registry.register("cache", failed, &["mysql"]).is_ok());
+
+        let result = registry.start().await;
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "storage registry failed: cache: storage connection error: refused"
+        );
+        assert_eq!(
+            events(&log),
+            vec!["start mysql", "stop cache", "stop mysql"]
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_start_failed_stop_failed() {
+        let log = Events::default();
+        let mut failed = FakeStorage::new("cache", &log);
+        failed.fail_start = true;
+        failed.fail_stop = true;
+
+        let mut registry = Registry::new(options());
+        assert!(registry.register("cache", failed, &[]).is_ok());
+
+        let result = registry.start().await;
+        assert_eq!(
+            result,
+            Err(StorageError::RegistryError(vec![
+                StorageFailure {
+                    name: "cache".to_string(),
+                    error: StorageError::ConnectionError("refused".to_string()),
+                },
+                StorageFailure {
+                    name: "cache".to_string(),
+                    error: StorageError::ConnectionError("broken".to_string()),
+                },
+            ]))
+        );
+        assert_eq!(events(&log), vec!["stop cache"])
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_shutdown_aggregated_errors() {
+        let log = Events::default();
+        let mut registry = Registry::new(options());
+        let handle = registry
+            .register("mysql", FakeStorage::new("mysql", &log), &[])
+            .unwrap();
+        assert!(registry
+            .register("rocksdb", FakeStorage::new("rocksdb", &log), &[])
+            .is_ok());
+        assert!(registry.start().await.is_ok());
+
+        handle.lock().await.fail_stop = true;
+        let result = registry.shutdown().await;
+        assert_eq!(
+            result,
+            Err(StorageError::RegistryError(vec![StorageFailure {
+                name: "mysql".to_string(),
+                error: StorageError::ConnectionError("broken".to_string()),
+            }]))
+        );
+        assert_eq!(
+            events(&log),
+            vec!["start mysql", "start rocksdb", "stop rocksdb", "stop mysql"]
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_start_timeout() {
+        let log = Events::default();
+        let mut slow = FakeStorage::new("postgres", &log);
+        slow.start_delay = Duration::from_millis(100);
+
+        let mut registry = Registry::new(options());
+        assert!(registry
+            .register("mysql", FakeStorage::new("mysql", &log), &[])
+            .is_ok());
+        assert!(registry.register("postgres", slow, &["mysql"]).is_ok());
+
+        let result = registry.start().await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("postgres: storage connection error: start timed out"));
+        assert_eq!(
+            events(&log),
+            vec!["start mysql", "stop postgres", "stop mysql"]
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_stop_timeout() {
+        let log = Events::default();
+        let mut slow = FakeStorage::new("postgres", &log);
+        slow.stop_delay = Duration::from_millis(100);
+
+        let mut registry = Registry::new(options());
+        assert!(registry
+            .register("mysql", FakeStorage::new("mysql", &log), &[])
+            .is_ok());
+        assert!(registry.register("postgres", slow, &["mysql"]).is_ok());
+        assert!(registry.start().await.is_ok());
+
+        let result = registry.shutdown().await;
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("postgres: storage connection error: stop timed out"));
+        assert_eq!(
+            events(&log),
+            vec!["start mysql", "start postgres", "stop mysql"]
+        )
+    }
+
+    #[test]
+    fn test_register_errors() {
+        let log = Events::default();
+        let mut registry = Registry::new(options());
+        assert!(registry
+            .register("mysql", FakeStorage::new("mysql", &log), &["postgres"])
+            .is_ok());
+        assert!(registry
+            .register("mysql", FakeStorage::new("mysql", &log), &[])
+            .is_err());
+        assert_eq!(registry.names(), vec!["mysql"]);
+        assert!(registry
+            .order()
+            .unwrap_err()
+            .to_string()
+            .contains("depends on unknown storage postgres"));
+
+        assert!(registry
+            .register("postgres", FakeStorage::new("postgres", &log), &["mysql"])
+            .is_ok());
+        assert!(registry.order().unwrap_err().to_string().contains("cycle"))
+    }
+}
@@ -0,0 +1,358 @@
+//! `retry` module provides a [`RetryPolicy`] used to establish storage connections
+//!
+//! It's common when the application and its databases started together, the databases
+//! are not ready yet to accept any connections. Rather than fail immediately, each failed
+//! attempt will be reported and retried with an exponential backoff. An error that will not be
+//! solved by retrying it, like an invalid option or a failed authentication, will stop it early.
+use core::future::Future;
+use core::time::Duration;
+use std::fmt;
+
+use rst_common::with_tokio::tokio::time::sleep;
+
+use crate::types::StorageError;
+
+/// `is_transient` used to check if given error may be solved by retrying the connection, which are
+/// all retryable errors and a [`StorageError::ConnectionError`], like a refused connection
+pub fn is_transient(err: &StorageError) -> bool {
+    err.is_retryable() || matches!(err, StorageError::ConnectionError(_))
+}
+
+/// `RetryAttempt` is a single failed attempt, including for the delay before the next attempt
+#[derive(Debug, PartialEq)]
+pub struct RetryAttempt {
+    pub attempt: u32,
+    pub error: StorageError,
+    pub delay: Option<Duration>,
+}
+
+impl fmt::Display for RetryAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "attempt {}: {}", self.attempt, self.error)
+    }
+}
+
+/// `RetryPolicy` used to configure how many attempts and how long the delays between them
+///
+/// - `max_attempts`, a maximum number of attempts including for the first one
+/// - `initial_delay`, a delay after the first failed attempt, it will be doubled on each attempt
+/// - `max_delay`, a maximum delay between attempts
+/// - `jitter`, a ratio (`0.0` - `1.0`) of the delay that will be randomized, used to prevent all
+///   instances retrying at the same time
+/// - `reporter`, an optional callback called on each failed attempt, by default all failed
+///   attempts will only be returned in the final error
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub initial_delay: Duration,
+    pub max_delay: Duration,
+    pub jitter: f64,
+    pub reporter: Option<fn(&RetryAttempt)>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 1,
+            initial_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(10),
+            jitter: 0.0,
+            reporter: None,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// `exponential` used to build a policy with given max attempts, an initial delay of `100ms`,
+    /// a max delay of `10s` and `20%` jitter
+    pub fn exponential(max_attempts: u32) -> Self {
+        Self {
+            max_attempts,
+            jitter: 0.2,
+            ..Self::default()
+        }
+    }
+
+    /// `delay` used to calculate the delay after given failed attempt, started from `1`
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let factor = 2u32.saturating_pow(attempt.saturating_sub(1));
+        let delay = self
+            .initial_delay
+            .saturating_mul(factor)
+            .min(self.max_delay);
+
+        // an invalid jitter, including NaN, will be ignored, it's rejected by `validate`
+        if self.jitter <= 0.0 || self.jitter > 1.0 || self.jitter.is_nan() {
+            return delay;
+        }
+
+        // randomize the delay between `delay * (1 - jitter)` and `delay`
+        delay.mul_f64(1.0 - self.jitter * fastrand::f64())
+    }
+
+    /// `validate` used to check the policy, `jitter` must be a ratio between `0.0` and `1.0`
+    pub fn validate(&self) -> Result<(), StorageError> {
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(StorageError::BuildOptionsError(format!(
+                "retry jitter must be between 0.0 and 1.0, got {}",
+                self.jitter
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// `run` used to call given callback until it succeed, reached the max attempts or failed
+    /// with an error that is not [`is_transient`]
+    ///
+    /// The final error will be a [`StorageError::ConnectionRetryError`] which contains all
+    /// failed attempts, unless there is only a single attempt
+    pub async fn run<T, F, Fut>(&self, mut callback: F) -> Result<T, StorageError>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, StorageError>>,
+    {
+        self.validate()?;
+
+        let max_attempts = self.max_attempts.max(1);
+        let mut attempts = Vec::new();
+
+        for attempt in 1..=max_attempts {
+            let error = match callback().await {
+                Ok(out) => return Ok(out),
+                Err(err) => err,
+            };
+
+            let transient = is_transient(&error);
+            if max_attempts == 1 || (!transient && attempts.is_empty()) {
+                return Err(error);
+            }
+
+            let delay = (transient && attempt < max_attempts).then(|| self.delay(attempt));
+            let failed = RetryAttempt {
+                attempt,
+                error,
+                delay,
+            };
+
+            if let Some(reporter) = self.reporter {
+                reporter(&failed);
+            }
+
+            attempts.push(failed);
+            match delay {
+                Some(delay) => sleep(delay).await,
+                None => break,
+            }
+        }
+
+        Err(StorageError::ConnectionRetryError(attempts))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use rst_common::with_tokio::tokio;
+
+    fn policy(max_attempts: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts,
+            initial_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(4),
+            jitter: 0.0,
+            reporter: Some(|_| {}),
+        }
+    }
+
+    #[test]
+    fn test_delay() {
+        let policy = policy(5);
+        assert_eq!(policy.delay(1), Duration::from_millis(1));
+        assert_eq!(policy.delay(2), Duration::from_millis(2));
+        assert_eq!(policy.delay(3), Duration::from_millis(4));
+        assert_eq!(policy.delay(10), Duration::from_millis(4))
+    }
+
+    #[test]
+    fn test_delay_jitter() {
+        let mut policy = policy(5);
+        policy.initial_delay = Duration::from_millis(100);
+        policy.max_delay = Duration::from_millis(100);
+        policy.jitter = 0.5;
+
+        for _ in 0..20 {
+            let delay = policy.delay(1);
+            assert!(delay >= Duration::from_millis(50) && delay <= Duration::from_millis(100));
+        }
+    }
+
+    #[test]
+    fn test_validate_jitter() {
+        let mut policy = policy(5);
+        for jitter in [f64::NAN, -0.1, 1.5] {
+            policy.jitter = jitter;
+            assert!(matches!(
+                policy.validate(),
+                Err(StorageError::BuildOptionsError(_))
+            ));
+            assert_eq!(policy.delay(1), Duration::from_millis(1));
+        }
+
+        policy.jitter = 1.0;
+        assert!(policy.validate().is_ok())
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_run_invalid_jitter() {
+        let calls = AtomicU32::new(0);
+        let mut policy = policy(3);
+        policy.jitter = f64::NAN;
+
+        let result = policy
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(())
+            })
+            .await;
+
+        assert!(matches!(result, Err(StorageError::BuildOptionsError(_))));
+        assert_eq!(calls.load(Ordering::SeqCst), 0)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_run_stop_on_non_transient() {
+        let calls = AtomicU32::new(0);
+        let result: Result<(), StorageError> = policy(3)
+            .run(|| async {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Err(StorageError::ConnectionRejectedError(
+                    "auth failed".to_string(),
+                ))
+            })
+            .await;
+
+        assert_eq!(
+            result,
+            Err(StorageError::ConnectionRejectedError(
+                "auth failed".to_string()
+            ))
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<(), StorageError> = policy(3)
+            .run(|| async {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return Err(StorageError::ConnectionError("refused".to_string()));
+                }
+
+                Err(StorageError::ConnectionRejectedError(
+                    "auth failed".to_string(),
+                ))
+            })
+            .await;
+
+        assert_eq!(
+            result,
+            Err(StorageError::ConnectionRetryError(vec![
+                RetryAttempt {
+                    attempt: 1,
+                    error: StorageError::ConnectionError("refused".to_string()),
+                    delay: Some(Duration::from_millis(1)),
+                },
+                RetryAttempt {
+                    attempt: 2,
+                    error: StorageError::ConnectionRejectedError("auth failed".to_string()),
+                    delay: None,
+                },
+            ]))
+        );
+        assert_eq!(calls.load(Ordering::SeqCst), 2)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_run_success_after_retries() {
+        let calls = AtomicU32::new(0);
+        let result = policy(3)
+            .run(|| async {
+                if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                    return Err(StorageError::ConnectionError("refused".to_string()));
+                }
+
+                Ok("connected")
+            })
+            .await;
+
+        assert_eq!(result, Ok("connected"));
+        assert_eq!(calls.load(Ordering::SeqCst), 3)
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_run_failed_with_history() {
+        let result: Result<(), StorageError> = policy(2)
+            .run(|| async { Err(StorageError::ConnectionError("refused".to_string())) })
+            .await;
+
+        assert_eq!(
+            result,
+            Err(StorageError::ConnectionRetryError(vec![
+                RetryAttempt {
+                    attempt: 1,
+                    error: StorageError::ConnectionError("refused".to_string()),
+                    delay: Some(Duration::from_millis(1)),
+                },
+                RetryAttempt {
+                    attempt: 2,
+                    error: StorageError::ConnectionError("refused".to_string()),
+                    delay: None,
+                },
+            ]))
+        );
+
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "storage connection failed after 2 attempts: attempt 1: storage connection error: refused, attempt 2: storage connection error: refused"
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_run_single_attempt() {
+        let result: Result<(), StorageError> = RetryPolicy::default()
+            .run(|| async { Err(StorageError::ConnectionError("refused".to_string())) })
+            .await;
+
+        assert_eq!(
+            result,
+            Err(StorageError::ConnectionError("refused".to_string()))
+        )
+    }
+
+    #[tokio::test(crate = "rst_common::with_tokio::tokio")]
+    async fn test_run_reporter() {
+        static REPORTED: AtomicU32 = AtomicU32::new(0);
+
+        let mut policy = policy(3);
+        policy.reporter = Some(|attempt| {
+            REPORTED.fetch_add(1, Ordering::SeqCst);
+            assert!(matches!(attempt.error, StorageError::ConnectionError(_)));
+        });
+
+        let result: Result<(), StorageError> = policy
+            .run(|| async { Err(StorageError::ConnectionError("refused".to_string())) })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(REPORTED.load(Ordering::SeqCst), 3);
+
+        policy.reporter = None;
+        let result: Result<(), StorageError> = policy
+            .run(|| async { Err(StorageError::ConnectionError("refused".to_string())) })
+            .await;
+
+        assert!(result.is_err());
+        assert_eq!(REPORTED.load(Ordering::SeqCst), 3)
+    }
+}
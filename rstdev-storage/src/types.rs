@@ -4,9 +4,15 @@
 use rst_common::standard::async_trait::async_trait;
 use rst_common::with_errors::thiserror::{self, Error};
 
+use crate::registry::StorageFailure;
+use crate::retry::RetryAttempt;
+
 /// `StorageError` is a list of error types designed specifically
 /// for common storage activities
+///
+/// New variants may be added in a minor release, so a `match` must include a wildcard arm
 #[derive(Debug, Error, PartialEq)]
+#[non_exhaustive]
 pub enum StorageError {
     #[error("storage connection error: {0}")]
     ConnectionError(String),
@@ -22,6 +28,90 @@ pub enum StorageError {
 
     #[error("options error: {0}")]
     BuildOptionsError(String),
+
+    #[error("storage migration error: {0}")]
+    MigrationError(String),
+
+    #[error("storage record not found")]
+    NotFoundError,
+
+    /// `UniqueViolationError` contains the violated constraint name when the database reports it
+    #[error("storage unique violation: {message}")]
+    UniqueViolationError {
+        constraint: Option<String>,
+        message: String,
+    },
+
+    #[error("storage foreign key violation: {message}")]
+    ForeignKeyViolationError {
+        constraint: Option<String>,
+        message: String,
+    },
+
+    #[error("storage check violation: {message}")]
+    CheckViolationError {
+        constraint: Option<String>,
+        message: String,
+    },
+
+    #[error("storage deadlock: {0}")]
+    DeadlockError(String),
+
+    #[error("storage serialization failure: {0}")]
+    SerializationError(String),
+
+    #[error("storage timeout: {0}")]
+    TimeoutError(String),
+
+    #[error("storage pool exhausted: {0}")]
+    PoolExhaustedError(String),
+
+    #[error("storage connection lost: {0}")]
+    ConnectionLostError(String),
+
+    /// `ConnectionRejectedError` used when the database refused a new connection and retrying it
+    /// will not help, like an authentication failure, an unknown database or a failed TLS handshake
+    #[error("storage connection rejected: {0}")]
+    ConnectionRejectedError(String),
+
+    /// `ConnectionRetryError` contains all failed attempts of a [`crate::retry::RetryPolicy`]
+    #[error("storage connection failed after {} attempts: {}", .0.len(), join_attempts(.0))]
+    ConnectionRetryError(Vec<RetryAttempt>),
+
+    /// `RegistryError` contains all failed storages of a [`crate::registry::Registry`]
+    #[error("storage registry failed: {}", join_failures(.0))]
+    RegistryError(Vec<StorageFailure>),
+}
+
+impl StorageError {
+    /// `is_retryable` used to check if the failed operation may succeed when it's retried, like
+    /// a deadlock, a serialization failure, a timeout, an exhausted pool or a lost connection
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            StorageError::DeadlockError(_)
+                | StorageError::SerializationError(_)
+                | StorageError::TimeoutError(_)
+                | StorageError::PoolExhaustedError(_)
+                | StorageError::ConnectionLostError(_)
+        )
+    }
+}
+
+fn join_attempts(attempts: &[RetryAttempt]) -> String {
+    attempts
+        .iter()
+        .map(|attempt| attempt.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn join_failures(failures: &[StorageFailure]) -> String {
+    failures
+        .iter()
+        .map(|failure| failure.to_string())
+        .collect::<Vec<String>>()
+        .join(", ")
 }
 
 /// `Storage` trait is a core abstraction for external storage
@@ -66,8 +156,31 @@ pub trait Storage {
         Err(StorageError::MethodNotImplementedError("ping".to_string()))
     }
 
+    /// `pinger` is an optional method used to get a [`Pinger`] which can ping the storage without
+    /// borrowing it, by default there is no pinger
+    ///
+    /// It will be used by the health monitor, so a shared storage will not be locked while it's pinged
+    fn pinger(&self) -> Option<Box<dyn Pinger>> {
+        None
+    }
+
+    /// `reconnect` is an optional method used to rebuild a dead storage connection
+    ///
+    /// This method will be used by the health monitor when the storage failed to be pinged
+    async fn reconnect(&mut self) -> Result<(), StorageError> {
+        Err(StorageError::MethodNotImplementedError(
+            "reconnect".to_string(),
+        ))
+    }
+
     /// `close` is an optional method used to close storage connection
     async fn close(&mut self) -> Result<(), StorageError> {
         Err(StorageError::MethodNotImplementedError("close".to_string()))
     }
 }
+
+/// `Pinger` is a detached handle used to ping a storage, like a clone of its connection pool
+#[async_trait]
+pub trait Pinger: Send + Sync {
+    async fn ping(&self) -> Result<(), StorageError>;
+}